@@ -0,0 +1,171 @@
+use crate::template::{Context, Template};
+use bytes::Bytes;
+use futures::stream;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub enum Body {
+    Template(Template),
+    // Read once and shared by every request without copying.
+    Bytes(Bytes),
+    Multipart(Vec<Field>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    name: String,
+    value: FieldValue,
+}
+
+#[derive(Debug, Clone)]
+enum FieldValue {
+    Text(Template),
+    File { file_name: String, content: Bytes },
+}
+
+impl Body {
+    pub fn templates(&self) -> Vec<&Template> {
+        match self {
+            Body::Template(template) => vec![template],
+            Body::Bytes(_) => Vec::new(),
+            Body::Multipart(fields) => fields
+                .iter()
+                .filter_map(|field| match &field.value {
+                    FieldValue::Text(template) => Some(template),
+                    FieldValue::File { .. } => None,
+                })
+                .collect(),
+        }
+    }
+
+    // What one request sends, with placeholders rendered.
+    pub fn render(&self, context: &Context) -> Payload {
+        match self {
+            Body::Template(template) => Payload {
+                parts: vec![Bytes::from(template.render(context))],
+                content_type: None,
+            },
+            Body::Bytes(content) => Payload {
+                parts: vec![content.clone()],
+                content_type: None,
+            },
+            Body::Multipart(fields) => {
+                let boundary = format!(
+                    "{:016x}-{:016x}-{:016x}-{:016x}",
+                    rand::random::<u64>(),
+                    rand::random::<u64>(),
+                    rand::random::<u64>(),
+                    rand::random::<u64>()
+                );
+
+                let mut parts = Vec::new();
+
+                for field in fields {
+                    let mut head = format!(
+                        "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+                        boundary,
+                        quote(&field.name)
+                    );
+
+                    let value = match &field.value {
+                        FieldValue::Text(template) => Bytes::from(template.render(context)),
+                        FieldValue::File { file_name, content } => {
+                            head.push_str(&format!("; filename=\"{}\"", quote(file_name)));
+                            content.clone()
+                        }
+                    };
+
+                    head.push_str("\r\n\r\n");
+
+                    parts.push(Bytes::from(head));
+                    parts.push(value);
+                    parts.push(Bytes::from_static(b"\r\n"));
+                }
+
+                parts.push(Bytes::from(format!("--{}--\r\n", boundary)));
+
+                Payload {
+                    parts,
+                    content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+                }
+            }
+        }
+    }
+}
+
+// A rendered body, kept in parts so uploaded files are sent without copying them.
+#[derive(Debug, Clone)]
+pub struct Payload {
+    parts: Vec<Bytes>,
+    content_type: Option<String>,
+}
+
+impl Payload {
+    pub fn len(&self) -> u64 {
+        self.parts.iter().map(|part| part.len() as u64).sum()
+    }
+
+    // Only a multipart form sets its own content type, which names its boundary.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn body(&self) -> hyper::Body {
+        match self.parts.as_slice() {
+            [part] => hyper::Body::from(part.clone()),
+            parts => {
+                let chunks: Vec<_> = parts.iter().cloned().map(Ok::<_, io::Error>).collect();
+
+                hyper::Body::wrap_stream(stream::iter(chunks))
+            }
+        }
+    }
+}
+
+// Form field and file names are quoted the way browsers do, escaping what would end them.
+fn quote(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+impl Field {
+    // NAME=VALUE for a text field or NAME=@PATH to upload a file.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (name, value) = value
+            .split_once('=')
+            .ok_or_else(|| format!("invalid field {}, expected NAME=VALUE", value))?;
+
+        let value = match value.strip_prefix('@') {
+            Some(path) => FieldValue::File {
+                file_name: Path::new(path)
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                content: load(path)?,
+            },
+            None => FieldValue::Text(value.parse()?),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+pub fn load(path: &str) -> Result<Bytes, String> {
+    fs::read(path)
+        .map(Bytes::from)
+        .map_err(|error| format!("cannot read {}: {}", path, error))
+}
+
+pub fn read_stdin() -> Bytes {
+    let mut data = Vec::new();
+
+    io::stdin().read_to_end(&mut data).expect("stdin");
+
+    Bytes::from(data)
+}
@@ -0,0 +1,185 @@
+use crate::extract::JsonPath;
+use crate::scenario::ScenarioCheck;
+use hyper::header::{HeaderMap, HeaderName};
+use regex::Regex;
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    Status(Vec<u16>),
+    Contains(String),
+    Regex(Regex),
+    Json {
+        path: JsonPath,
+        expression: String,
+        expected: String,
+    },
+    Header(HeaderName),
+    MaxSize(u64),
+}
+
+impl Check {
+    pub fn from_scenario_check(check: &ScenarioCheck) -> Result<Vec<Self>, String> {
+        let mut checks = Vec::new();
+
+        if !check.status.is_empty() {
+            checks.push(Check::Status(check.status.clone()));
+        }
+
+        if let Some(text) = &check.contains {
+            checks.push(Check::Contains(text.to_string()));
+        }
+
+        if let Some(pattern) = &check.regex {
+            let regex = Regex::new(pattern)
+                .map_err(|error| format!("invalid regex {}: {}", pattern, error))?;
+
+            checks.push(Check::Regex(regex));
+        }
+
+        for (path, value) in &check.json {
+            checks.push(Check::Json {
+                path: path.parse()?,
+                expression: path.to_string(),
+                expected: value.to_string(),
+            });
+        }
+
+        for header in &check.headers {
+            let name = header
+                .parse()
+                .map_err(|_| format!("invalid header {}", header))?;
+
+            checks.push(Check::Header(name));
+        }
+
+        if let Some(size) = check.max_size {
+            checks.push(Check::MaxSize(size));
+        }
+
+        Ok(checks)
+    }
+
+    pub fn needs_body(&self) -> bool {
+        matches!(
+            self,
+            Check::Contains(_) | Check::Regex(_) | Check::Json { .. }
+        )
+    }
+
+    // Describes the failure, or None when the response passes.
+    pub fn verify(
+        &self,
+        status: u16,
+        headers: &HeaderMap,
+        body: &[u8],
+        size: u64,
+    ) -> Option<String> {
+        match self {
+            Check::Status(codes) if !codes.contains(&status) => {
+                Some(format!("status {} not in {:?}", status, codes))
+            }
+            Check::Contains(text) if !String::from_utf8_lossy(body).contains(text.as_str()) => {
+                Some(format!("body does not contain {}", text))
+            }
+            Check::Regex(regex) if !regex.is_match(&String::from_utf8_lossy(body)) => {
+                Some(format!("body does not match {}", regex))
+            }
+            Check::Json {
+                path,
+                expression,
+                expected,
+            } => match path.find(body) {
+                Some(value) if value == *expected => None,
+                Some(value) => Some(format!(
+                    "{} is {}, expected {}",
+                    expression, value, expected
+                )),
+                None => Some(format!("{} not found", expression)),
+            },
+            Check::Header(name) if !headers.contains_key(name) => {
+                Some(format!("header {} missing", name))
+            }
+            Check::MaxSize(maximum) if size > *maximum => {
+                Some(format!("body size {} over {}", size, maximum))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::CONTENT_TYPE;
+
+    const BODY: &[u8] = br#"{"status": "ok", "count": 3}"#;
+
+    fn verify(check: Check) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+
+        check.verify(200, &headers, BODY, BODY.len() as u64)
+    }
+
+    fn json(path: &str, expected: &str) -> Check {
+        Check::Json {
+            path: path.parse().unwrap(),
+            expression: path.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    #[test]
+    fn verifies_status() {
+        assert_eq!(verify(Check::Status(vec![200, 201])), None);
+        assert_eq!(
+            verify(Check::Status(vec![404])),
+            Some("status 200 not in [404]".to_string())
+        );
+    }
+
+    #[test]
+    fn verifies_body_text() {
+        assert_eq!(verify(Check::Contains("\"ok\"".to_string())), None);
+        assert_eq!(
+            verify(Check::Contains("error".to_string())),
+            Some("body does not contain error".to_string())
+        );
+        assert_eq!(
+            verify(Check::Regex(Regex::new(r#""count": \d+"#).unwrap())),
+            None
+        );
+        assert_eq!(
+            verify(Check::Regex(Regex::new("^ok$").unwrap())),
+            Some("body does not match ^ok$".to_string())
+        );
+    }
+
+    #[test]
+    fn verifies_json_values() {
+        assert_eq!(verify(json("$.status", "ok")), None);
+        assert_eq!(verify(json("$.count", "3")), None);
+        assert_eq!(
+            verify(json("$.status", "failed")),
+            Some("$.status is ok, expected failed".to_string())
+        );
+        assert_eq!(
+            verify(json("$.missing", "ok")),
+            Some("$.missing not found".to_string())
+        );
+    }
+
+    #[test]
+    fn verifies_headers_and_size() {
+        assert_eq!(verify(Check::Header(CONTENT_TYPE)), None);
+        assert_eq!(
+            verify(Check::Header(HeaderName::from_static("etag"))),
+            Some("header etag missing".to_string())
+        );
+        assert_eq!(verify(Check::MaxSize(BODY.len() as u64)), None);
+        assert_eq!(
+            verify(Check::MaxSize(10)),
+            Some(format!("body size {} over 10", BODY.len()))
+        );
+    }
+}
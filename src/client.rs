@@ -0,0 +1,229 @@
+use crate::body::Payload;
+use crate::connection::{self, Connections, Connector};
+use crate::error::{ErrorKind, RequestError};
+use crate::settings::{HttpVersion, Settings};
+use cookie_store::CookieStore;
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use url::Url;
+
+const MAX_REDIRECTS: usize = 10;
+
+// Sends requests the way a browser would, following redirects and, within a session,
+// keeping cookies.
+#[derive(Clone)]
+pub struct Client {
+    client: hyper::Client<Connector, Body>,
+    cookies: Option<Arc<Mutex<CookieStore>>>,
+}
+
+impl Client {
+    pub fn new(settings: &Settings) -> Self {
+        let alpn = settings.http_version == HttpVersion::Http2;
+
+        let connector = Connector::new(
+            settings.tls.connector(alpn),
+            settings.tls.server_name.clone(),
+            settings.connect_timeout,
+        );
+
+        let mut builder = hyper::Client::builder();
+
+        // The client does not act on the protocol picked through ALPN, so HTTP/2 is always
+        // spoken from the start.
+        if settings.http_version != HttpVersion::Http1 {
+            builder.http2_only(true);
+        }
+
+        // No idle connections kept means a new connection for every request.
+        if !settings.keep_alive {
+            builder.pool_max_idle_per_host(0);
+        } else if let Some(max_idle) = settings.max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+
+        if let Some(timeout) = settings.pool_idle_timeout {
+            builder.pool_idle_timeout(timeout);
+        }
+
+        // Each worker is a virtual user, so a session keeps its own cookies.
+        let cookies = match settings.session {
+            true => Some(Arc::new(Mutex::new(CookieStore::default()))),
+            false => None,
+        };
+
+        Self {
+            client: builder.build(connector),
+            cookies,
+        }
+    }
+
+    // The final response and the connections used on the way to it.
+    pub async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        mut headers: HeaderMap,
+        payload: Option<Payload>,
+        start: Instant,
+    ) -> Result<(Response<Body>, Connections), RequestError> {
+        let mut url = Url::parse(url).map_err(|error| RequestError {
+            kind: ErrorKind::Other,
+            message: format!("invalid url {}: {}", url, error),
+        })?;
+
+        let mut method = method;
+        let mut payload = payload;
+        let mut connections = Connections::default();
+
+        if let Some(content_type) = payload.as_ref().and_then(|payload| payload.content_type()) {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(content_type).expect("header"),
+            );
+        }
+
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send_once(&method, &url, &headers, &payload).await?;
+
+            connections = connections.add(connection::claim(&response, start));
+
+            let status = response.status();
+
+            let location = match status {
+                StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT => response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| url.join(location).ok()),
+                _ => None,
+            };
+
+            let next = match location {
+                Some(next) if next.scheme() == "http" || next.scheme() == "https" => next,
+                _ => return Ok((response, connections)),
+            };
+
+            // Only 307 and 308 repeat the request as it was, the others fetch the new one.
+            if status != StatusCode::TEMPORARY_REDIRECT && status != StatusCode::PERMANENT_REDIRECT
+            {
+                if method != Method::HEAD {
+                    method = Method::GET;
+                }
+
+                payload = None;
+
+                for name in &[
+                    header::TRANSFER_ENCODING,
+                    header::CONTENT_ENCODING,
+                    header::CONTENT_TYPE,
+                    header::CONTENT_LENGTH,
+                ] {
+                    headers.remove(name);
+                }
+            }
+
+            // Credentials and the host name are only meant for the server they were set for.
+            if next.host_str() != url.host_str()
+                || next.port_or_known_default() != url.port_or_known_default()
+            {
+                for name in &[
+                    header::AUTHORIZATION,
+                    header::COOKIE,
+                    HeaderName::from_static("cookie2"),
+                    header::PROXY_AUTHORIZATION,
+                    header::WWW_AUTHENTICATE,
+                    header::HOST,
+                ] {
+                    headers.remove(name);
+                }
+            }
+
+            url = next;
+        }
+
+        Err(RequestError {
+            kind: ErrorKind::Redirect,
+            message: "too many redirects".to_string(),
+        })
+    }
+
+    async fn send_once(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        payload: &Option<Payload>,
+    ) -> Result<Response<Body>, RequestError> {
+        let mut url = url.clone();
+        url.set_fragment(None);
+
+        let uri: Uri = url.as_str().parse().map_err(|error| RequestError {
+            kind: ErrorKind::Other,
+            message: format!("invalid url {}: {}", url, error),
+        })?;
+
+        let body = match payload {
+            Some(payload) => payload.body(),
+            None => Body::empty(),
+        };
+
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(uri)
+            .body(body)
+            .expect("request");
+
+        *request.headers_mut() = headers.clone();
+
+        if let Some(payload) = payload {
+            request
+                .headers_mut()
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(payload.len()));
+        }
+
+        // A cookie header given with the request is sent as it is.
+        if let Some(cookies) = &self.cookies {
+            if !request.headers().contains_key(header::COOKIE) {
+                let cookie = cookies
+                    .lock()
+                    .expect("cookies")
+                    .get_request_cookies(&url)
+                    .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+
+                if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+                    if !cookie.is_empty() {
+                        request.headers_mut().insert(header::COOKIE, cookie);
+                    }
+                }
+            }
+        }
+
+        let response = self.client.request(request).await?;
+
+        if let Some(cookies) = &self.cookies {
+            let set_cookies = response
+                .headers()
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .filter_map(|value| cookie::Cookie::parse(value.to_string()).ok())
+                .collect::<Vec<_>>();
+
+            cookies
+                .lock()
+                .expect("cookies")
+                .store_response_cookies(set_cookies.into_iter(), &url);
+        }
+
+        Ok(response)
+    }
+}
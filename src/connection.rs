@@ -0,0 +1,327 @@
+use chrono::Duration;
+use hyper::client::connect::{Connected, Connection};
+use hyper::service::Service;
+use hyper::{Body, Response, Uri};
+use std::error::Error;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{self, Poll};
+use std::time;
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tls::{TlsConnector, TlsStream};
+
+// Opens the connections of a client, timing each step of setting them up.
+#[derive(Clone)]
+pub struct Connector {
+    tls: TlsConnector,
+    server_name: Option<String>,
+    connect_timeout: Option<time::Duration>,
+}
+
+pub struct Stream {
+    inner: Inner,
+    setup: Setup,
+}
+
+enum Inner {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+// How long opening a connection took, handed to every response sent over it.
+#[derive(Debug, Clone)]
+struct Setup {
+    started: Instant,
+    times: Connections,
+    claimed: Arc<AtomicBool>,
+}
+
+// The connections a request opened or reused, and how long it waited for them to open.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Connections {
+    pub opened: u64,
+    pub reused: u64,
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+}
+
+impl Connections {
+    // A request that follows a redirect to another server also waits for that connection.
+    pub fn add(self, other: Connections) -> Connections {
+        let add = |a: Option<Duration>, b: Option<Duration>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+
+        Connections {
+            opened: self.opened + other.opened,
+            reused: self.reused + other.reused,
+            dns: add(self.dns, other.dns),
+            connect: add(self.connect, other.connect),
+            tls: add(self.tls, other.tls),
+        }
+    }
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+impl Connector {
+    pub fn new(
+        tls: native_tls::TlsConnector,
+        server_name: Option<String>,
+        connect_timeout: Option<time::Duration>,
+    ) -> Self {
+        Self {
+            tls: TlsConnector::from(tls),
+            server_name,
+            connect_timeout,
+        }
+    }
+
+    async fn connect(self, uri: Uri) -> Result<Stream, BoxError> {
+        let started = Instant::now();
+
+        let host = uri.host().ok_or("url has no host")?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let tls = uri.scheme_str() == Some("https");
+        let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+
+        let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|error| io::Error::new(error.kind(), format!("dns error: {}", error)))?
+            .collect();
+
+        let resolved = Instant::now();
+
+        let stream = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect_tcp(&addresses))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))??,
+            None => connect_tcp(&addresses).await?,
+        };
+
+        let connected = Instant::now();
+
+        let inner = match tls {
+            true => {
+                // The URL host is only where to connect when another server name is given.
+                let server_name = self.server_name.as_deref().unwrap_or(host);
+
+                let stream = self
+                    .tls
+                    .connect(server_name, stream)
+                    .await
+                    .map_err(|error| io::Error::other(format!("tls error: {}", error)))?;
+
+                Inner::Tls(Box::new(stream))
+            }
+            false => Inner::Plain(stream),
+        };
+
+        let between = |from: Instant, to: Instant| Duration::from_std(to - from).ok();
+
+        let times = Connections {
+            opened: 1,
+            reused: 0,
+            dns: between(started, resolved),
+            connect: between(resolved, connected),
+            tls: match tls {
+                true => between(connected, Instant::now()),
+                false => None,
+            },
+        };
+
+        Ok(Stream {
+            inner,
+            setup: Setup {
+                started,
+                times,
+                claimed: Arc::new(AtomicBool::new(false)),
+            },
+        })
+    }
+}
+
+// Addresses are tried in the order the resolver returned them.
+async fn connect_tcp(addresses: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut last_error = None;
+
+    for address in addresses {
+        match TcpStream::connect(address).await {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "dns error: no addresses found")
+    }))
+}
+
+impl Service<Uri> for Connector {
+    type Response = Stream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Stream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), BoxError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(uri))
+    }
+}
+
+impl Connection for Stream {
+    fn connected(&self) -> Connected {
+        Connected::new().extra(self.setup.clone())
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        context: &mut task::Context<'_>,
+        buffer: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.inner {
+            Inner::Plain(stream) => Pin::new(stream).poll_read(context, buffer),
+            Inner::Tls(stream) => Pin::new(stream).poll_read(context, buffer),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        context: &mut task::Context<'_>,
+        buffer: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.inner {
+            Inner::Plain(stream) => Pin::new(stream).poll_write(context, buffer),
+            Inner::Tls(stream) => Pin::new(stream).poll_write(context, buffer),
+        }
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.inner {
+            Inner::Plain(stream) => Pin::new(stream).poll_flush(context),
+            Inner::Tls(stream) => Pin::new(stream).poll_flush(context),
+        }
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        context: &mut task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.inner {
+            Inner::Plain(stream) => Pin::new(stream).poll_shutdown(context),
+            Inner::Tls(stream) => Pin::new(stream).poll_shutdown(context),
+        }
+    }
+}
+
+// The first response over a connection opened it and later ones reused it. A request only
+// spent the setup times when it was already waiting while the connection opened, rather
+// than getting a connection another request started.
+pub fn claim(response: &Response<Body>, start: Instant) -> Connections {
+    let setup = match response.extensions().get::<Setup>() {
+        Some(setup) => setup,
+        None => return Connections::default(),
+    };
+
+    if setup.claimed.swap(true, Ordering::Relaxed) {
+        return Connections {
+            reused: 1,
+            ..Connections::default()
+        };
+    }
+
+    match setup.started >= start {
+        true => setup.times,
+        false => Connections {
+            opened: 1,
+            ..Connections::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Answers every request on a connection until the client closes it.
+    async fn serve(mut listener: TcpListener) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            tokio::spawn(async move {
+                let mut buffer = [0; 1024];
+
+                while let Ok(count) = stream.read(&mut buffer).await {
+                    if count == 0 {
+                        break;
+                    }
+
+                    let response = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+
+                    if stream.write_all(response).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_and_times_connections_of_a_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: Uri = format!(
+            "http://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        )
+        .parse()
+        .unwrap();
+
+        tokio::spawn(serve(listener));
+
+        let tls = native_tls::TlsConnector::new().unwrap();
+        let client = Client::builder().build::<_, Body>(Connector::new(tls, None, None));
+        let mut connections = Vec::new();
+
+        for _ in 0..3 {
+            let start = Instant::now();
+            let response = client.get(url.clone()).await.unwrap();
+
+            connections.push(claim(&response, start));
+
+            hyper::body::to_bytes(response.into_body()).await.unwrap();
+        }
+
+        assert_eq!((connections[0].opened, connections[0].reused), (1, 0));
+        assert!(connections[0].dns.is_some());
+        assert!(connections[0].connect.is_some());
+        assert!(connections[0].tls.is_none());
+
+        for connection in &connections[1..] {
+            assert_eq!((connection.opened, connection.reused), (0, 1));
+            assert!(connection.dns.is_none() && connection.connect.is_none());
+        }
+    }
+}
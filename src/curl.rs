@@ -0,0 +1,399 @@
+use crate::body;
+use crate::scenario::{ScenarioCheck, ScenarioRequest};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug)]
+pub struct CurlCommand {
+    pub request: ScenarioRequest,
+    pub insecure: bool,
+}
+
+// Options that only change what curl prints or how it follows redirects.
+const IGNORED: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-L",
+    "--location",
+    "-g",
+    "--globoff",
+];
+
+impl CurlCommand {
+    // Parses a command as copied from browser developer tools, such as
+    // curl 'https://example.com/api' -H 'Accept: application/json' --data-raw '{"id":1}'.
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let words = split(command)?;
+        let mut words = words.iter().map(|word| word.as_str());
+
+        if words.next() != Some("curl") {
+            return Err("the command must start with curl".to_string());
+        }
+
+        let mut method = None;
+        let mut url = None;
+        let mut headers = BTreeMap::new();
+        let mut data: Vec<Vec<u8>> = Vec::new();
+        let mut query = false;
+        let mut insecure = false;
+
+        while let Some(word) = words.next() {
+            let mut value = |name: &str| {
+                words
+                    .next()
+                    .map(|value| value.to_string())
+                    .ok_or_else(|| format!("curl option {} needs a value", name))
+            };
+
+            match word {
+                "-X" | "--request" => method = Some(value(word)?),
+                "-H" | "--header" => {
+                    let header = value(word)?;
+                    let (name, value) = header
+                        .split_once(':')
+                        .ok_or_else(|| format!("invalid curl header {}", header))?;
+
+                    headers.insert(name.trim().to_string(), value.trim().to_string());
+                }
+                "-d" | "--data" | "--data-ascii" => data.push(read_data(&value(word)?, true)?),
+                "--data-binary" => data.push(read_data(&value(word)?, false)?),
+                "--data-raw" => data.push(value(word)?.into_bytes()),
+                "-u" | "--user" => {
+                    let credentials = base64::encode(value(word)?);
+                    headers.insert(
+                        "Authorization".to_string(),
+                        format!("Basic {}", credentials),
+                    );
+                }
+                "-b" | "--cookie" => {
+                    headers.insert("Cookie".to_string(), value(word)?);
+                }
+                "-A" | "--user-agent" => {
+                    headers.insert("User-Agent".to_string(), value(word)?);
+                }
+                "-e" | "--referer" => {
+                    headers.insert("Referer".to_string(), value(word)?);
+                }
+                "--url" => url = Some(value(word)?),
+                "--compressed" => {
+                    headers.insert(
+                        "Accept-Encoding".to_string(),
+                        "deflate, gzip, br".to_string(),
+                    );
+                }
+                "-k" | "--insecure" => insecure = true,
+                "-G" | "--get" => query = true,
+                word if IGNORED.contains(&word) => (),
+                word if word.starts_with('-') => {
+                    return Err(format!("unsupported curl option {}", word));
+                }
+                word => url = Some(word.to_string()),
+            }
+        }
+
+        let mut url = url.ok_or("the curl command has no url")?;
+
+        // Like curl, several data options are joined as form fields, and -G moves them to
+        // the query string.
+        let data = match data.is_empty() {
+            true => None,
+            false => Some(data.join(&b'&')),
+        };
+
+        let data = match (query, data) {
+            (true, Some(data)) => {
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str(&String::from_utf8_lossy(&data));
+                None
+            }
+            (_, data) => data,
+        };
+
+        // curl sends data as a form unless told otherwise.
+        let has_content_type = headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-type"));
+
+        if data.is_some() && !has_content_type {
+            headers.insert(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string(),
+            );
+        }
+
+        let method = method.unwrap_or_else(|| match data {
+            Some(_) => "POST".to_string(),
+            None => "GET".to_string(),
+        });
+
+        let request = ScenarioRequest {
+            name: None,
+            weight: 1,
+            method,
+            url,
+            data: None,
+            body_file: None,
+            headers,
+            extract: BTreeMap::new(),
+            check: ScenarioCheck::default(),
+            delay: None,
+            literal: true,
+            body: data.map(Bytes::from),
+        };
+
+        Ok(Self { request, insecure })
+    }
+}
+
+// @FILE sends the contents of a file, which curl strips of line breaks unless the data
+// is binary.
+fn read_data(value: &str, text: bool) -> Result<Vec<u8>, String> {
+    let path = match value.strip_prefix('@') {
+        Some(path) => path,
+        None => return Ok(value.as_bytes().to_vec()),
+    };
+
+    let mut content = body::load(path)?.to_vec();
+
+    if text {
+        content.retain(|byte| *byte != b'\r' && *byte != b'\n');
+    }
+
+    Ok(content)
+}
+
+// Splits a command into words like a POSIX shell, including the $'...' quoting browsers
+// use for data with special characters and backslashes continuing a line.
+fn split(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match char {
+            char if char.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\\' => match chars.next() {
+                Some('\n') | None => (),
+                Some(char) => word.get_or_insert_with(String::new).push(char),
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(char) => word.push(char),
+                        None => return Err("unclosed ' in curl command".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(char @ ('"' | '\\' | '$' | '`')) => word.push(char),
+                            Some('\n') => (),
+                            Some(char) => {
+                                word.push('\\');
+                                word.push(char);
+                            }
+                            None => return Err("unclosed \" in curl command".to_string()),
+                        },
+                        Some(char) => word.push(char),
+                        None => return Err("unclosed \" in curl command".to_string()),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => word.push(unescape(&mut chars)?),
+                        Some(char) => word.push(char),
+                        None => return Err("unclosed $' in curl command".to_string()),
+                    }
+                }
+            }
+            char => word.get_or_insert_with(String::new).push(char),
+        }
+    }
+
+    words.extend(word);
+
+    Ok(words)
+}
+
+fn unescape(chars: &mut Peekable<Chars<'_>>) -> Result<char, String> {
+    let invalid = || "invalid escape in curl command".to_string();
+
+    let char = match chars.next().ok_or_else(invalid)? {
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        '0' => '\0',
+        'x' => hex(chars, 2).ok_or_else(invalid)?,
+        'u' => hex(chars, 4).ok_or_else(invalid)?,
+        'U' => hex(chars, 8).ok_or_else(invalid)?,
+        char => char,
+    };
+
+    Ok(char)
+}
+
+// Up to the given number of hex digits as a character.
+fn hex(chars: &mut Peekable<Chars<'_>>, digits: usize) -> Option<char> {
+    let mut code = 0;
+    let mut count = 0;
+
+    while count < digits {
+        match chars.peek().and_then(|char| char.to_digit(16)) {
+            Some(digit) => {
+                code = code * 16 + digit;
+                count += 1;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+
+    match count {
+        0 => None,
+        _ => std::char::from_u32(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn words(command: &str) -> Vec<String> {
+        split(command).unwrap()
+    }
+
+    fn body(curl: &CurlCommand) -> &[u8] {
+        curl.request.body.as_deref().unwrap()
+    }
+
+    #[test]
+    fn splits_quoted_words() {
+        assert_eq!(
+            words(r#"curl 'a b' "c \"d\" \x" e\ f"#),
+            vec!["curl", "a b", r#"c "d" \x"#, "e f"]
+        );
+        assert_eq!(words("curl a\\\n  -k"), vec!["curl", "a", "-k"]);
+        assert_eq!(words("curl ''"), vec!["curl", ""]);
+    }
+
+    #[test]
+    fn splits_ansi_c_quoting() {
+        assert_eq!(
+            words(r#"curl $'{\"a\":\n\x41é}'"#),
+            vec!["curl", "{\"a\":\nAé}"]
+        );
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes() {
+        assert!(split("curl 'a").is_err());
+        assert!(split("curl \"a").is_err());
+        assert!(split("curl $'a").is_err());
+    }
+
+    #[test]
+    fn parses_a_copied_request() {
+        let curl = CurlCommand::parse(
+            "curl 'https://example.com/api' -H 'Accept: application/json' \
+             -H 'Content-Type: application/json' --data-raw '{\"id\":1}' --compressed -k",
+        )
+        .unwrap();
+
+        assert!(curl.insecure);
+        assert!(curl.request.literal);
+        assert_eq!(curl.request.method, "POST");
+        assert_eq!(curl.request.url, "https://example.com/api");
+        assert_eq!(curl.request.headers["Accept"], "application/json");
+        assert_eq!(curl.request.headers["Content-Type"], "application/json");
+        assert_eq!(curl.request.headers["Accept-Encoding"], "deflate, gzip, br");
+        assert_eq!(body(&curl), br#"{"id":1}"#);
+    }
+
+    #[test]
+    fn sends_data_as_a_form_by_default() {
+        let curl = CurlCommand::parse("curl https://example.com -d a=1 -d b=2").unwrap();
+
+        assert_eq!(
+            curl.request.headers["Content-Type"],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(body(&curl), b"a=1&b=2");
+    }
+
+    #[test]
+    fn moves_data_to_the_query_with_get() {
+        let curl = CurlCommand::parse("curl -G https://example.com/?x=0 -d a=1").unwrap();
+
+        assert_eq!(curl.request.method, "GET");
+        assert_eq!(curl.request.url, "https://example.com/?x=0&a=1");
+        assert!(curl.request.body.is_none());
+        assert!(!curl.request.headers.contains_key("Content-Type"));
+    }
+
+    #[test]
+    fn reads_data_files_except_for_raw_data() {
+        let path = std::env::temp_dir().join(format!("curl-data-{}", std::process::id()));
+        fs::write(&path, "a=1\r\nb=2\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let data = CurlCommand::parse(&format!("curl https://example.com -d @{}", path)).unwrap();
+        let binary =
+            CurlCommand::parse(&format!("curl https://example.com --data-binary @{}", path))
+                .unwrap();
+        let raw =
+            CurlCommand::parse(&format!("curl https://example.com --data-raw @{}", path)).unwrap();
+
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(body(&data), b"a=1b=2");
+        assert_eq!(body(&binary), b"a=1\r\nb=2\n");
+        assert_eq!(body(&raw), format!("@{}", path).as_bytes());
+    }
+
+    #[test]
+    fn encodes_basic_credentials() {
+        let curl = CurlCommand::parse("curl -X PUT -u user:secret https://example.com").unwrap();
+
+        assert_eq!(curl.request.method, "PUT");
+        assert_eq!(
+            curl.request.headers["Authorization"],
+            "Basic dXNlcjpzZWNyZXQ="
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert!(CurlCommand::parse("wget https://example.com").is_err());
+        assert!(CurlCommand::parse("curl -H").is_err());
+        assert!(CurlCommand::parse("curl -H nocolon https://example.com").is_err());
+        assert!(CurlCommand::parse("curl --proxy p https://example.com").is_err());
+        assert!(CurlCommand::parse("curl -k").is_err());
+        assert!(CurlCommand::parse("curl https://example.com -d @/nonexistent").is_err());
+    }
+}
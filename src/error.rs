@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub struct RequestError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum ErrorKind {
+    Dns,
+    ConnectionRefused,
+    ConnectionReset,
+    Tls,
+    Timeout,
+    Decode,
+    Redirect,
+    Check,
+    Header,
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Dns => "DNS Failure",
+            ErrorKind::ConnectionRefused => "Connection Refused",
+            ErrorKind::ConnectionReset => "Connection Reset",
+            ErrorKind::Tls => "TLS Error",
+            ErrorKind::Timeout => "Timeout",
+            ErrorKind::Decode => "Body Decode",
+            ErrorKind::Redirect => "Redirect Loop",
+            ErrorKind::Check => "Check Failed",
+            ErrorKind::Header => "Invalid Header",
+            ErrorKind::Other => "Failed",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl ErrorKind {
+    // Failures are told apart by the I/O error or the messages down the chain, such as the
+    // ones the connector gives its resolver and handshake errors.
+    pub fn classify(error: &(dyn Error + 'static)) -> Self {
+        let mut messages = Vec::new();
+        let mut source = Some(error);
+
+        while let Some(cause) = source {
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                match io_error.kind() {
+                    io::ErrorKind::ConnectionRefused => return ErrorKind::ConnectionRefused,
+                    io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe => return ErrorKind::ConnectionReset,
+                    io::ErrorKind::TimedOut => return ErrorKind::Timeout,
+                    _ => (),
+                }
+            }
+
+            messages.push(cause.to_string().to_lowercase());
+            source = cause.source();
+        }
+
+        let mentions = |words: &[&str]| {
+            messages
+                .iter()
+                .any(|message| words.iter().any(|word| message.contains(word)))
+        };
+
+        let body = matches!(
+            error.downcast_ref::<hyper::Error>(),
+            Some(error) if error.is_parse() || error.is_incomplete_message()
+        );
+
+        if mentions(&[
+            "dns error",
+            "failed to lookup address",
+            "name or service not known",
+        ]) {
+            ErrorKind::Dns
+        } else if mentions(&["tls", "ssl", "certificate", "handshake"]) {
+            ErrorKind::Tls
+        } else if mentions(&[
+            "connection reset",
+            "connection closed",
+            "channel closed",
+            "broken pipe",
+        ]) {
+            ErrorKind::ConnectionReset
+        } else if body {
+            ErrorKind::Decode
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+impl From<hyper::Error> for RequestError {
+    fn from(error: hyper::Error) -> Self {
+        Self {
+            kind: ErrorKind::classify(&error),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use crate::stats::{self, Stats};
+use crate::summary::Summary;
+use crate::worker::WorkerMessage;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub path: String,
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Sample<'a> {
+    worker: usize,
+    endpoint: &'a str,
+    intended_time: Option<String>,
+    start_time: String,
+    stop_time: String,
+    elapsed_us: u64,
+    request_bytes: u64,
+    response_bytes: u64,
+    status: Option<&'a str>,
+    version: Option<&'a str>,
+    error_kind: Option<String>,
+    error: Option<&'a str>,
+    check: Option<&'a str>,
+    body: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SampleWriter {
+    Json(BufWriter<File>),
+    Csv(Box<csv::Writer<File>>),
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "csv" => Format::Csv,
+            _ => Format::Json,
+        }
+    }
+
+    pub fn from_path(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".csv") {
+            Format::Csv
+        } else {
+            Format::Json
+        }
+    }
+}
+
+impl Output {
+    // The file is created up front, so an unwritable path fails before the run rather than
+    // after it.
+    pub fn new(path: String, format: Option<Format>) -> Result<Self, String> {
+        let format = format.unwrap_or_else(|| Format::from_path(&path));
+
+        if path != "-" {
+            File::create(&path).map_err(|error| format!("cannot write {}: {}", path, error))?;
+        }
+
+        Ok(Self { path, format })
+    }
+
+    // A path of - writes to stdout.
+    pub fn is_stdout(&self) -> bool {
+        self.path == "-"
+    }
+}
+
+impl SampleWriter {
+    pub fn create(output: &Output) -> Self {
+        let file = File::create(&output.path).expect("samples");
+
+        match output.format {
+            Format::Json => SampleWriter::Json(BufWriter::new(file)),
+            Format::Csv => SampleWriter::Csv(Box::new(csv::Writer::from_writer(file))),
+        }
+    }
+
+    pub fn write(&mut self, message: &WorkerMessage, endpoint: &str) {
+        let metric = &message.metric;
+
+        let sample = Sample {
+            worker: message.id,
+            endpoint,
+            intended_time: metric.intended_time.map(|time| time.to_rfc3339()),
+            start_time: metric.start_time.to_rfc3339(),
+            stop_time: metric.stop_time.to_rfc3339(),
+            elapsed_us: stats::micros(metric.elapsed_time),
+            request_bytes: metric.request_bytes,
+            response_bytes: metric.response_bytes,
+            status: metric.status_code.as_deref(),
+            version: metric.version.as_deref(),
+            error_kind: metric.error_kind.map(|kind| kind.to_string()),
+            error: metric.error_message.as_deref(),
+            check: metric.check_error.as_deref(),
+            body: metric
+                .response_body
+                .as_ref()
+                .map(|body| String::from_utf8_lossy(body).to_string()),
+        };
+
+        match self {
+            SampleWriter::Json(writer) => {
+                serde_json::to_writer(&mut *writer, &sample).expect("samples");
+                writeln!(writer).expect("samples");
+            }
+            SampleWriter::Csv(writer) => writer.serialize(&sample).expect("samples"),
+        }
+    }
+}
+
+pub fn write_summary(summary: &Summary, output: &Output) {
+    let mut writer: Box<dyn Write> = match output.is_stdout() {
+        true => Box::new(io::stdout()),
+        false => Box::new(BufWriter::new(File::create(&output.path).expect("output"))),
+    };
+
+    match output.format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, summary).expect("output");
+            writeln!(writer).expect("output");
+        }
+        Format::Csv => write_summary_csv(summary, &mut writer),
+    }
+
+    writer.flush().expect("output");
+}
+
+fn write_summary_csv(summary: &Summary, output: impl Write) {
+    let mut writer = csv::Writer::from_writer(output);
+
+    let mut header: Vec<String> = [
+        "endpoint",
+        "count",
+        "minimum_us",
+        "mean_us",
+        "maximum_us",
+        "stddev_us",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect();
+
+    for (percentile, _) in &summary.stats.time_percentiles {
+        header.push(format!("p{}_us", percentile));
+    }
+
+    header.push("corrected_mean_us".to_string());
+
+    for (percentile, _) in &summary.stats.corrected_percentiles {
+        header.push(format!("corrected_p{}_us", percentile));
+    }
+
+    for phase in &summary.stats.phases {
+        header.push(format!("{}_mean_us", phase.key));
+    }
+
+    header.push("request_bytes".to_string());
+    header.push("response_bytes".to_string());
+    header.push("size_mean".to_string());
+    header.push("status".to_string());
+
+    writer.write_record(&header).expect("output");
+
+    writer
+        .write_record(summary_record("total", &summary.stats))
+        .expect("output");
+
+    if summary.endpoints.len() > 1 {
+        for (name, stats) in &summary.endpoints {
+            writer
+                .write_record(summary_record(name, stats))
+                .expect("output");
+        }
+    }
+
+    writer.flush().expect("output");
+}
+
+fn summary_record(name: &str, stats: &Stats) -> Vec<String> {
+    let mut record = vec![
+        name.to_string(),
+        stats.count.to_string(),
+        stats.time_minimum.to_string(),
+        stats.time_mean.to_string(),
+        stats.time_maximum.to_string(),
+        stats.time_stddev.to_string(),
+    ];
+
+    for (_, value) in &stats.time_percentiles {
+        record.push(value.to_string());
+    }
+
+    record.push(stats.corrected_mean.to_string());
+
+    for (_, value) in &stats.corrected_percentiles {
+        record.push(value.to_string());
+    }
+
+    for phase in &stats.phases {
+        record.push(phase.mean.to_string());
+    }
+
+    record.push(stats.request_bytes.to_string());
+    record.push(stats.response_bytes.to_string());
+    record.push(stats.size_mean.to_string());
+
+    let status: Vec<String> = stats
+        .status
+        .iter()
+        .map(|(status, count)| format!("{}={}", status, count))
+        .collect();
+
+    record.push(status.join(";"));
+
+    record
+}
@@ -0,0 +1,183 @@
+use crate::scenario::ScenarioExtract;
+use hyper::header::{HeaderMap, HeaderName};
+use regex::Regex;
+use serde_json::Value;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    pub name: String,
+    source: Source,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    Json(JsonPath),
+    Regex(Regex),
+    Header(HeaderName),
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Extractor {
+    pub fn new(name: &str, extract: &ScenarioExtract) -> Result<Self, String> {
+        let source = match extract {
+            ScenarioExtract::Json(path) => Source::Json(path.parse()?),
+            ScenarioExtract::Regex(pattern) => Source::Regex(
+                Regex::new(pattern)
+                    .map_err(|error| format!("invalid regex {}: {}", pattern, error))?,
+            ),
+            ScenarioExtract::Header(header) => Source::Header(
+                header
+                    .parse()
+                    .map_err(|_| format!("invalid header {}", header))?,
+            ),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            source,
+        })
+    }
+
+    pub fn needs_body(&self) -> bool {
+        !matches!(self.source, Source::Header(_))
+    }
+
+    pub fn extract(&self, headers: &HeaderMap, body: &[u8]) -> Option<String> {
+        match &self.source {
+            Source::Json(path) => path.find(body),
+            // The first capture group when there is one, otherwise the whole match.
+            Source::Regex(regex) => {
+                let body = String::from_utf8_lossy(body);
+                let captures = regex.captures(&body)?;
+
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|value| value.as_str().to_string())
+            }
+            Source::Header(name) => headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string()),
+        }
+    }
+}
+
+// A JSONPath subset of child keys and array indexes, such as $.items[0].id or $['id'].
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid json path {}", path);
+
+        let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+        let mut segments = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let end = tail.find(['.', '[']).unwrap_or(tail.len());
+
+                if end == 0 {
+                    return Err(invalid());
+                }
+
+                segments.push(Segment::Key(tail[..end].to_string()));
+                rest = &tail[end..];
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let end = tail.find(']').ok_or_else(invalid)?;
+                let inner = &tail[..end];
+
+                let segment = match unquote(inner) {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(inner.parse().map_err(|_| invalid())?),
+                };
+
+                segments.push(segment);
+                rest = &tail[end + 1..];
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl JsonPath {
+    // Strings are returned without their quotes, anything else as JSON.
+    pub fn find(&self, body: &[u8]) -> Option<String> {
+        let value: Value = serde_json::from_slice(body).ok()?;
+
+        let value = self
+            .segments
+            .iter()
+            .try_fold(&value, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(index),
+            })?;
+
+        match value {
+            Value::String(value) => Some(value.to_string()),
+            value => Some(value.to_string()),
+        }
+    }
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    ['\'', '"'].iter().find_map(|quote| {
+        value
+            .strip_prefix(*quote)
+            .and_then(|value| value.strip_suffix(*quote))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"id": "a1", "items": [{"id": 7, "tags": ["x"]}], "odd key": true}"#;
+
+    fn find(path: &str) -> Option<String> {
+        path.parse::<JsonPath>().unwrap().find(BODY)
+    }
+
+    #[test]
+    fn finds_keys_and_indexes() {
+        assert_eq!(find("$.id"), Some("a1".to_string()));
+        assert_eq!(find("$.items[0].id"), Some("7".to_string()));
+        assert_eq!(find("$.items[0].tags"), Some(r#"["x"]"#.to_string()));
+    }
+
+    #[test]
+    fn finds_quoted_keys() {
+        assert_eq!(find("$['odd key']"), Some("true".to_string()));
+        assert_eq!(find(r#"$["items"][0]["id"]"#), Some("7".to_string()));
+    }
+
+    #[test]
+    fn finds_nothing_for_missing_values() {
+        assert_eq!(find("$.missing"), None);
+        assert_eq!(find("$.items[1]"), None);
+        assert_eq!("$.id".parse::<JsonPath>().unwrap().find(b"not json"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        assert!("items[0]".parse::<JsonPath>().is_err());
+        assert!("$..id".parse::<JsonPath>().is_err());
+        assert!("$.items[first]".parse::<JsonPath>().is_err());
+        assert!("$.items[0".parse::<JsonPath>().is_err());
+        assert!("$items".parse::<JsonPath>().is_err());
+    }
+}
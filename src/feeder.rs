@@ -0,0 +1,210 @@
+use crate::export::Format;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub type Row = HashMap<String, String>;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FeederMode {
+    Sequential,
+    Random,
+    Partitioned,
+}
+
+#[derive(Debug, Clone)]
+pub struct Feeder {
+    pub columns: Vec<String>,
+    rows: Arc<Vec<Row>>,
+    mode: FeederMode,
+    wrap: bool,
+    workers: usize,
+    next: Arc<Vec<AtomicUsize>>,
+}
+
+impl FeederMode {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "random" => FeederMode::Random,
+            "partitioned" => FeederMode::Partitioned,
+            _ => FeederMode::Sequential,
+        }
+    }
+}
+
+impl Feeder {
+    pub fn new(rows: Vec<Row>, mode: FeederMode, wrap: bool, workers: usize) -> Self {
+        let mut columns: Vec<String> = rows.iter().flat_map(|row| row.keys().cloned()).collect();
+
+        columns.sort();
+        columns.dedup();
+
+        // Sequential and random modes share the first cursor, partitioned mode has one per worker.
+        let next = (0..workers.max(1)).map(|_| AtomicUsize::new(0)).collect();
+
+        Self {
+            columns,
+            rows: Arc::new(rows),
+            mode,
+            wrap,
+            workers: workers.max(1),
+            next: Arc::new(next),
+        }
+    }
+
+    // The row for the next request of a worker, or None once the feed ran out and should stop.
+    pub fn next(&self, worker: usize) -> Option<&Row> {
+        let count = self.rows.len();
+
+        if count == 0 {
+            return None;
+        }
+
+        let index = match self.mode {
+            FeederMode::Random => thread_rng().gen_range(0, count),
+            FeederMode::Sequential => self.cursor(0, count)?,
+            FeederMode::Partitioned => {
+                let partition = (worker - 1) % self.workers;
+
+                // Rows partition, partition + workers, partition + 2 * workers and so on.
+                let partition_count = (count + self.workers - 1 - partition) / self.workers;
+
+                partition + self.cursor(partition, partition_count)? * self.workers
+            }
+        };
+
+        self.rows.get(index)
+    }
+
+    fn cursor(&self, cursor: usize, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        let index = self.next[cursor].fetch_add(1, Ordering::Relaxed);
+
+        if index < count {
+            Some(index)
+        } else if self.wrap {
+            Some(index % count)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn load(path: &str, format: Format) -> Result<Vec<Row>, String> {
+    let file = File::open(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+
+    let rows = match format {
+        Format::Csv => load_csv(file),
+        Format::Json => load_jsonl(file),
+    }
+    .map_err(|error| format!("invalid data file {}: {}", path, error))?;
+
+    if rows.is_empty() {
+        return Err(format!("data file {} has no rows", path));
+    }
+
+    Ok(rows)
+}
+
+fn load_csv(file: File) -> Result<Vec<Row>, String> {
+    let mut reader = csv::Reader::from_reader(file);
+
+    reader
+        .deserialize::<Row>()
+        .map(|row| row.map_err(|error| error.to_string()))
+        .collect()
+}
+
+// Lines are numbered from one in errors, as editors show them.
+fn load_jsonl(file: File) -> Result<Vec<Row>, String> {
+    let mut rows = Vec::new();
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|error| error.to_string())?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let object: HashMap<String, serde_json::Value> = serde_json::from_str(&line)
+            .map_err(|error| format!("line {}: {}", number + 1, error))?;
+
+        let row = object
+            .into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(value) => (key, value),
+                value => (key, value.to_string()),
+            })
+            .collect();
+
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feeder(count: usize, mode: FeederMode, wrap: bool, workers: usize) -> Feeder {
+        let rows = (0..count)
+            .map(|index| {
+                let mut row = Row::new();
+                row.insert("id".to_string(), index.to_string());
+                row
+            })
+            .collect();
+
+        Feeder::new(rows, mode, wrap, workers)
+    }
+
+    fn ids(feeder: &Feeder, worker: usize, requests: usize) -> Vec<Option<String>> {
+        (0..requests)
+            .map(|_| feeder.next(worker).map(|row| row["id"].to_string()))
+            .collect()
+    }
+
+    fn some(ids: &[&str]) -> Vec<Option<String>> {
+        ids.iter().map(|id| Some(id.to_string())).collect()
+    }
+
+    #[test]
+    fn shares_rows_in_sequence() {
+        let feeder = feeder(3, FeederMode::Sequential, true, 2);
+
+        assert_eq!(ids(&feeder, 1, 2), some(&["0", "1"]));
+        assert_eq!(ids(&feeder, 2, 2), some(&["2", "0"]));
+    }
+
+    #[test]
+    fn gives_each_worker_its_own_rows() {
+        let feeder = feeder(5, FeederMode::Partitioned, false, 2);
+
+        assert_eq!(ids(&feeder, 1, 3), some(&["0", "2", "4"]));
+        assert_eq!(ids(&feeder, 2, 2), some(&["1", "3"]));
+        assert_eq!(feeder.next(1), None);
+        assert_eq!(feeder.next(2), None);
+    }
+
+    #[test]
+    fn wraps_within_a_partition() {
+        let feeder = feeder(5, FeederMode::Partitioned, true, 2);
+
+        assert_eq!(ids(&feeder, 2, 3), some(&["1", "3", "1"]));
+    }
+
+    #[test]
+    fn leaves_workers_beyond_the_rows_without_any() {
+        let feeder = feeder(2, FeederMode::Partitioned, true, 3);
+
+        assert_eq!(ids(&feeder, 2, 2), some(&["1", "1"]));
+        assert_eq!(feeder.next(3), None);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::scenario::{Scenario, ScenarioCheck, ScenarioRequest};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::time;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HarMode {
+    Session,
+    Weighted,
+}
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    #[serde(default)]
+    time: f64,
+    request: HarRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    #[serde(default)]
+    mime_type: String,
+    text: Option<String>,
+}
+
+// Headers the client sets itself from the URL and the body.
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "transfer-encoding"];
+
+impl HarMode {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "weighted" => HarMode::Weighted,
+            _ => HarMode::Session,
+        }
+    }
+}
+
+// The recorded requests as scenario steps run in order, or as requests picked by weight,
+// sent as they were recorded. With timing, each request first waits as long as the browser
+// was idle before sending it.
+pub fn load(path: &str, mode: HarMode, timing: bool) -> Result<Scenario, String> {
+    let file = File::open(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+
+    let har: Har = serde_json::from_reader(file)
+        .map_err(|error| format!("invalid HAR file {}: {}", path, error))?;
+
+    scenario(har, path, mode, timing)
+}
+
+fn scenario(har: Har, path: &str, mode: HarMode, timing: bool) -> Result<Scenario, String> {
+    let mut entries = Vec::new();
+
+    for entry in har.log.entries {
+        let started_time = DateTime::parse_from_rfc3339(&entry.started_date_time)
+            .map_err(|_| format!("invalid HAR time {}", entry.started_date_time))?;
+
+        // Pages also record data: URLs and WebSocket upgrades, which are not replayed.
+        if entry.request.url.starts_with("http://") || entry.request.url.starts_with("https://") {
+            entries.push((started_time, entry));
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(format!("HAR file {} has no HTTP requests", path));
+    }
+
+    entries.sort_by_key(|(started_time, _)| *started_time);
+
+    let mut requests = Vec::new();
+    let mut finished_time: Option<DateTime<FixedOffset>> = None;
+
+    for (started_time, entry) in entries {
+        // Browsers send many requests at once, so the wait is measured from the last
+        // response to finish rather than from the previous request.
+        let delay = match (timing, finished_time) {
+            (true, Some(finished_time)) => (started_time - finished_time).to_std().ok(),
+            _ => None,
+        };
+
+        let stopped_time = started_time + Duration::microseconds((entry.time * 1000.0) as i64);
+        finished_time = finished_time.max(Some(stopped_time));
+
+        requests.push(request(entry.request, delay));
+    }
+
+    let scenario = match mode {
+        HarMode::Session => Scenario {
+            requests: Vec::new(),
+            steps: requests,
+        },
+        HarMode::Weighted => Scenario {
+            requests,
+            steps: Vec::new(),
+        },
+    };
+
+    Ok(scenario)
+}
+
+fn request(request: HarRequest, delay: Option<time::Duration>) -> ScenarioRequest {
+    let mut headers: BTreeMap<String, String> = BTreeMap::new();
+
+    for header in request.headers {
+        let name = header.name.to_lowercase();
+
+        // HTTP/2 requests are recorded with pseudo-headers such as :path as well.
+        if name.starts_with(':') || SKIPPED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+
+        // A header recorded more than once is sent once with its values joined.
+        let separator = if name == "cookie" { "; " } else { ", " };
+
+        let recorded = headers
+            .iter_mut()
+            .find(|(recorded, _)| recorded.eq_ignore_ascii_case(&name));
+
+        match recorded.map(|(_, value)| value) {
+            Some(value) => {
+                value.push_str(separator);
+                value.push_str(&header.value);
+            }
+            None => {
+                headers.insert(header.name, header.value);
+            }
+        }
+    }
+
+    let body = request.post_data.and_then(|post_data| {
+        let has_content_type = headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("content-type"));
+
+        if !has_content_type && !post_data.mime_type.is_empty() {
+            headers.insert("Content-Type".to_string(), post_data.mime_type);
+        }
+
+        post_data.text.map(Bytes::from)
+    });
+
+    ScenarioRequest {
+        name: None,
+        weight: 1,
+        method: request.method,
+        url: request.url,
+        data: None,
+        body_file: None,
+        headers,
+        extract: BTreeMap::new(),
+        check: ScenarioCheck::default(),
+        delay,
+        literal: true,
+        body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(entries: &str, mode: HarMode, timing: bool) -> Result<Scenario, String> {
+        let json = format!(r#"{{"log": {{"entries": [{}]}}}}"#, entries);
+
+        scenario(
+            serde_json::from_str(&json).unwrap(),
+            "test.har",
+            mode,
+            timing,
+        )
+    }
+
+    fn entry(started: &str, time: f64, url: &str, headers: &str) -> String {
+        format!(
+            r#"{{"startedDateTime": "{}", "time": {}, "request": {{"method": "GET", "url": "{}", "headers": [{}]}}}}"#,
+            started, time, url, headers
+        )
+    }
+
+    #[test]
+    fn joins_repeated_headers_and_skips_the_others() {
+        let headers = r#"
+            {"name": ":path", "value": "/"},
+            {"name": "Host", "value": "example.com"},
+            {"name": "Accept", "value": "text/html"},
+            {"name": "accept", "value": "*/*"},
+            {"name": "Cookie", "value": "a=1"},
+            {"name": "Cookie", "value": "b=2"}"#;
+
+        let entries = entry(
+            "2026-10-10T10:00:00.000Z",
+            10.0,
+            "https://example.com/",
+            headers,
+        );
+        let scenario = load(&entries, HarMode::Session, false).unwrap();
+        let headers = &scenario.steps[0].headers;
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["Accept"], "text/html, */*");
+        assert_eq!(headers["Cookie"], "a=1; b=2");
+    }
+
+    #[test]
+    fn waits_from_the_last_response_to_finish() {
+        let entries = [
+            entry(
+                "2026-10-10T10:00:00.000Z",
+                500.0,
+                "https://example.com/",
+                "",
+            ),
+            entry(
+                "2026-10-10T10:00:00.100Z",
+                100.0,
+                "https://example.com/a.js",
+                "",
+            ),
+            entry(
+                "2026-10-10T10:00:00.800Z",
+                100.0,
+                "https://example.com/b",
+                "",
+            ),
+            entry(
+                "2026-10-10T10:00:00.850Z",
+                100.0,
+                "https://example.com/c",
+                "",
+            ),
+        ]
+        .join(",");
+
+        let scenario = load(&entries, HarMode::Session, true).unwrap();
+        let delays: Vec<_> = scenario.steps.iter().map(|step| step.delay).collect();
+
+        assert_eq!(
+            delays,
+            vec![None, None, Some(time::Duration::from_millis(300)), None,]
+        );
+
+        let scenario = load(&entries, HarMode::Session, false).unwrap();
+
+        assert!(scenario.steps.iter().all(|step| step.delay.is_none()));
+    }
+
+    #[test]
+    fn keeps_only_http_requests_in_recorded_order() {
+        let entries = [
+            entry("2026-10-10T10:00:01.000Z", 1.0, "https://example.com/b", ""),
+            entry(
+                "2026-10-10T10:00:00.000Z",
+                1.0,
+                "data:image/png;base64,AAAA",
+                "",
+            ),
+            entry("2026-10-10T10:00:00.500Z", 1.0, "https://example.com/a", ""),
+        ]
+        .join(",");
+
+        let scenario = load(&entries, HarMode::Weighted, false).unwrap();
+        let urls: Vec<_> = scenario
+            .requests
+            .iter()
+            .map(|request| &request.url)
+            .collect();
+
+        assert!(scenario.steps.is_empty());
+        assert_eq!(urls, ["https://example.com/a", "https://example.com/b"]);
+
+        let entries = entry("2026-10-10T10:00:00.000Z", 1.0, "ws://example.com/", "");
+
+        assert!(load(&entries, HarMode::Session, false).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::export;
+use crate::settings::Settings;
+use crate::stats::format_time;
+use crate::summary::{self, Collector, Summary};
+use crate::worker::{WorkerCommand, WorkerMessage};
+use std::time;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+pub async fn render(
+    settings: &Settings,
+    mut receiver: mpsc::UnboundedReceiver<WorkerMessage>,
+    sender: watch::Sender<WorkerCommand>,
+) -> Summary {
+    let mut collector = Collector::new(settings);
+
+    let mut progress_time = Instant::now();
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    'render: loop {
+        collector.drain(&mut receiver);
+
+        if collector.finished() {
+            break 'render;
+        }
+
+        if progress_time.elapsed() >= time::Duration::from_secs(1) {
+            let summary = summary::compute(&collector, settings, 0);
+
+            eprintln!("{}", progress(&summary));
+
+            progress_time = Instant::now();
+        }
+
+        tokio::select! {
+            _ = &mut ctrl_c => break 'render,
+            _ = tokio::time::delay_for(time::Duration::from_millis(250)) => (),
+        };
+    }
+
+    receiver.close();
+
+    let _ = sender.broadcast(WorkerCommand::Stop);
+
+    let summary = summary::compute(&collector, settings, 0);
+
+    // With the summary file written to stdout the text summary moves to stderr.
+    match &settings.output {
+        Some(output) if output.is_stdout() => eprint!("{}", summary),
+        _ => print!("{}", summary),
+    }
+
+    if let Some(output) = &settings.output {
+        export::write_summary(&summary, output);
+    }
+
+    summary
+}
+
+fn progress(summary: &Summary) -> String {
+    let mut line = format!(
+        "[{:>4}s] {:>3}% {} requests, {:.2}req/s",
+        summary.elapsed_seconds, summary.progress_percent, summary.total_count, summary.rate
+    );
+
+    for (percentile, value) in &summary.stats.time_percentiles {
+        line.push_str(&format!(", p{} {}", percentile, format_time(*value)));
+    }
+
+    for (status, count) in &summary.stats.status {
+        line.push_str(&format!(", {}: {}", status, count));
+    }
+
+    if let Some(stage) = &summary.stage {
+        line.push_str(&format!(", {}", stage));
+    }
+
+    line
+}
@@ -1,12 +1,30 @@
 use crate::settings::Settings;
 use crate::worker::{WorkerCommand, WorkerMessage};
 use clap::{load_yaml, App, AppSettings};
+use std::process;
 use tokio::sync::{mpsc, watch};
 
+mod body;
+mod check;
+mod client;
+mod connection;
+mod curl;
+mod error;
+mod export;
+mod extract;
+mod feeder;
+mod har;
+mod headless;
 mod metric;
+mod profile;
+mod replay;
+mod scenario;
 mod settings;
 mod stats;
 mod summary;
+mod template;
+mod threshold;
+mod tls;
 mod ui;
 mod view;
 mod worker;
@@ -26,5 +44,13 @@ async fn main() {
 
     worker::collect_metrics(&settings, message_sender, command_receiver);
 
-    ui::render(&settings, message_receiver, command_sender).await;
+    let summary = if settings.headless {
+        headless::render(&settings, message_receiver, command_sender).await
+    } else {
+        ui::render(&settings, message_receiver, command_sender).await
+    };
+
+    if !summary.passed() {
+        process::exit(1);
+    }
 }
@@ -1,15 +1,42 @@
+use crate::check::Check;
+use crate::client::Client;
+use crate::error::{ErrorKind, RequestError};
 use crate::settings::Settings;
+use crate::template::Context;
 use chrono::{DateTime, Duration, Utc};
-use reqwest::Client;
+use hyper::body::HttpBody;
+use hyper::{Body, Response};
+use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
+use std::time;
+use std::time::Instant;
 
 #[derive(Debug)]
 pub struct RequestMetric {
+    pub endpoint: usize,
+    pub intended_time: Option<DateTime<Utc>>,
     pub start_time: DateTime<Utc>,
     pub stop_time: DateTime<Utc>,
     pub elapsed_time: Duration,
+    pub connections_opened: u64,
+    pub connections_reused: u64,
+    pub dns_time: Option<Duration>,
+    pub connect_time: Option<Duration>,
+    pub tls_time: Option<Duration>,
+    pub ttfb_time: Duration,
+    pub body_time: Duration,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub response_body: Option<Vec<u8>>,
+    pub extracted: HashMap<String, String>,
     pub status_code: Option<String>,
+    pub version: Option<String>,
     pub error_message: Option<String>,
+    pub error_kind: Option<ErrorKind>,
+    pub check_error: Option<String>,
+    // The expected status codes were checked, so a 4xx or 5xx one may be a success.
+    pub status_checked: bool,
 }
 
 impl fmt::Display for RequestMetric {
@@ -25,6 +52,7 @@ impl fmt::Display for RequestMetric {
         let error = self
             .error_message
             .as_ref()
+            .or(self.check_error.as_ref())
             .map(|error_message| error_message.to_string())
             .unwrap_or("".to_string());
 
@@ -33,43 +61,256 @@ impl fmt::Display for RequestMetric {
 }
 
 impl RequestMetric {
-    pub async fn collect_metric(client: &Client, settings: &Settings) -> Self {
-        let method = settings.method.clone();
-        let url = settings.url.clone();
+    pub async fn collect_metric(
+        client: &Client,
+        settings: &Settings,
+        endpoint: usize,
+        context: &Context,
+        intended_time: Option<DateTime<Utc>>,
+    ) -> Self {
+        let request_settings = &settings.endpoints[endpoint];
 
-        let request = client.request(method, url);
+        let method = request_settings.method.clone();
+        let url = request_settings.url.render(context);
 
-        let request = request.headers(settings.headers.clone());
+        let mut headers = request_settings.headers.clone();
+        let mut header_error = None;
 
-        let request = match &settings.data {
-            Some(data) => request.body(data.to_string()),
-            None => request,
-        };
+        // A placeholder may render a value no header can carry, such as one with a newline,
+        // and the request is not sent without it.
+        for (name, template) in &request_settings.header_templates {
+            match template.render(context).parse() {
+                Ok(value) => {
+                    headers.insert(name.clone(), value);
+                }
+                Err(_) => {
+                    header_error = Some(RequestError {
+                        kind: ErrorKind::Header,
+                        message: format!("invalid value of header {}", name),
+                    });
+                    break;
+                }
+            }
+        }
+
+        let payload = request_settings
+            .body
+            .as_ref()
+            .map(|body| body.render(context));
+
+        let request_bytes = payload.as_ref().map(|payload| payload.len()).unwrap_or(0);
 
         let start_time = Utc::now();
+        let start = Instant::now();
+
+        // The whole request, body included, has to finish within the timeout.
+        let deadline = settings.timeout.map(|timeout| start + timeout);
+
+        let result = match header_error {
+            Some(error) => Err(error),
+            None => {
+                let send = client.send(method, &url, headers, payload, start);
+
+                Self::read_timeout(settings.read_timeout, deadline, send).await
+            }
+        };
+
+        let response_time = Utc::now();
+
+        let (result, connections) = match result {
+            Ok((response, connections)) => (Ok(response), connections),
+            Err(error) => (Err(error), Default::default()),
+        };
+
+        let (status_code, version) = match &result {
+            Ok(response) => (
+                Some(response.status().to_string()),
+                Some(format!("{:?}", response.version())),
+            ),
+            Err(_) => (None, None),
+        };
+
+        let extractors = &request_settings.extractors;
+        let checks = &request_settings.checks;
 
-        let result = request.send().await;
+        let status_checked = checks.iter().any(|check| matches!(check, Check::Status(_)));
+
+        // Headers are only kept when something inspects the response.
+        let response_head = match &result {
+            Ok(response) if !extractors.is_empty() || !checks.is_empty() => {
+                Some((response.status().as_u16(), response.headers().clone()))
+            }
+            _ => None,
+        };
+
+        let needs_body = extractors.iter().any(|extractor| extractor.needs_body())
+            || checks.iter().any(|check| check.needs_body());
+
+        let mut response_bytes = 0;
+        let mut response_body = if settings.keep_body || needs_body {
+            Some(Vec::new())
+        } else {
+            None
+        };
+
+        let result = match result {
+            Ok(response) => {
+                Self::read_body(
+                    response,
+                    settings.read_timeout,
+                    deadline,
+                    &mut response_bytes,
+                    response_body.as_mut(),
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        };
 
         let stop_time = Utc::now();
 
+        let mut extracted = HashMap::new();
+        let mut check_error = None;
+
+        if let (Ok(_), Some((status, headers))) = (&result, &response_head) {
+            let body = response_body.as_deref().unwrap_or_default();
+
+            for extractor in extractors {
+                if let Some(value) = extractor.extract(headers, body) {
+                    extracted.insert(extractor.name.to_string(), value);
+                }
+            }
+
+            check_error = checks
+                .iter()
+                .find_map(|check| check.verify(*status, headers, body, response_bytes));
+        }
+
+        if !settings.keep_body {
+            response_body = None;
+        }
+
         let elapsed_time = stop_time.signed_duration_since(start_time);
+        let ttfb_time = response_time.signed_duration_since(start_time);
+        let body_time = stop_time.signed_duration_since(response_time);
 
-        let status_code = match &result {
-            Ok(response) => Some(response.status().to_string()),
-            Err(_) => None,
+        let (error_message, error_kind) = match &result {
+            Ok(_) if check_error.is_some() => (None, Some(ErrorKind::Check)),
+            Ok(_) => (None, None),
+            Err(error) => (Some(error.message.to_string()), Some(error.kind)),
         };
 
-        let error_message = match &result {
-            Ok(_) => None,
-            Err(error) => Some(error.to_string()),
+        Self {
+            endpoint,
+            intended_time,
+            start_time,
+            stop_time,
+            elapsed_time,
+            connections_opened: connections.opened,
+            connections_reused: connections.reused,
+            dns_time: connections.dns,
+            connect_time: connections.connect,
+            tls_time: connections.tls,
+            ttfb_time,
+            body_time,
+            request_bytes,
+            response_bytes,
+            response_body,
+            extracted,
+            status_code,
+            version,
+            error_message,
+            error_kind,
+            check_error,
+            status_checked,
+        }
+    }
+
+    async fn read_body(
+        mut response: Response<Body>,
+        read_timeout: Option<time::Duration>,
+        deadline: Option<Instant>,
+        bytes: &mut u64,
+        mut body: Option<&mut Vec<u8>>,
+    ) -> Result<(), RequestError> {
+        let body_stream = response.body_mut();
+
+        while let Some(chunk) = Self::read_timeout(read_timeout, deadline, async {
+            body_stream
+                .data()
+                .await
+                .transpose()
+                .map_err(RequestError::from)
+        })
+        .await?
+        {
+            *bytes += chunk.len() as u64;
+
+            if let Some(body) = body.as_mut() {
+                body.extend_from_slice(&chunk);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fails a request whose server sends nothing for longer than the read timeout, or that
+    // is still going when the deadline of the whole request passes.
+    async fn read_timeout<T>(
+        read_timeout: Option<time::Duration>,
+        deadline: Option<Instant>,
+        future: impl Future<Output = Result<T, RequestError>>,
+    ) -> Result<T, RequestError> {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        let (timeout, error) = match (read_timeout, remaining) {
+            (Some(read_timeout), Some(remaining)) if remaining < read_timeout => {
+                (remaining, Self::deadline_error())
+            }
+            (Some(read_timeout), _) => (
+                read_timeout,
+                RequestError {
+                    kind: ErrorKind::Timeout,
+                    message: format!("no response data for {}ms", read_timeout.as_millis()),
+                },
+            ),
+            (None, Some(remaining)) => (remaining, Self::deadline_error()),
+            (None, None) => return future.await,
         };
 
-        Self {
-            start_time: start_time,
-            stop_time: stop_time,
-            elapsed_time: elapsed_time,
-            status_code: status_code,
-            error_message: error_message,
+        match tokio::time::timeout(timeout, future).await {
+            Ok(result) => result,
+            Err(_) => Err(error),
+        }
+    }
+
+    fn deadline_error() -> RequestError {
+        RequestError {
+            kind: ErrorKind::Timeout,
+            message: "operation timed out".to_string(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        if self.error_kind.is_some() {
+            return true;
+        }
+
+        match &self.status_code {
+            Some(_) if self.status_checked => false,
+            Some(status_code) => status_code.starts_with('4') || status_code.starts_with('5'),
+            None => true,
+        }
+    }
+
+    // Intended start to completion, so time queued behind a stalled request is not omitted.
+    pub fn response_time(&self) -> Duration {
+        match self.intended_time {
+            Some(intended_time) => self
+                .stop_time
+                .signed_duration_since(intended_time)
+                .max(self.elapsed_time),
+            None => self.elapsed_time,
         }
     }
 }
@@ -0,0 +1,309 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub duration: u64,
+    pub rate: Option<u64>,
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub stages: Vec<Stage>,
+    rates: Option<Vec<f64>>,
+    concurrencies: Option<Vec<f64>>,
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(2, ':');
+
+        let duration = Self::parse_duration(parts.next().unwrap_or_default())?;
+
+        let mut stage = Self {
+            duration,
+            rate: None,
+            concurrency: None,
+        };
+
+        for target in parts.next().unwrap_or_default().split(',') {
+            let target = target.trim();
+
+            if target.is_empty() {
+                continue;
+            }
+
+            let (key, number) = match target.find('=') {
+                Some(index) => (&target[..index], &target[index + 1..]),
+                None => return Err(format!("invalid stage target {}", target)),
+            };
+
+            match key {
+                "rate" => stage.rate = Some(Self::parse_number(number)?),
+                "concurrency" => stage.concurrency = Some(Self::parse_number(number)? as usize),
+                _ => return Err(format!("unknown stage target {}", key)),
+            }
+        }
+
+        Ok(stage)
+    }
+}
+
+impl Stage {
+    fn parse_duration(value: &str) -> Result<u64, String> {
+        let (number, scale) = if let Some(number) = value.strip_suffix('h') {
+            (number, 3600)
+        } else if let Some(number) = value.strip_suffix('m') {
+            (number, 60)
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, 1)
+        } else {
+            (value, 1)
+        };
+
+        number
+            .parse::<u64>()
+            .map(|number| number * scale)
+            .map_err(|_| format!("invalid stage duration {}", value))
+    }
+
+    fn parse_number(value: &str) -> Result<u64, String> {
+        value
+            .parse::<u64>()
+            .map_err(|_| format!("invalid stage target {}", value))
+    }
+}
+
+impl Profile {
+    // Each stage ramps linearly from where the previous one ended, starting from the
+    // given rate and concurrency, or from zero when a stage sets them.
+    pub fn new(stages: Vec<Stage>, rate: Option<u64>, concurrency: Option<usize>) -> Self {
+        let rates = Self::points(&stages, rate.map(|rate| rate as f64), |stage| {
+            stage.rate.map(|rate| rate as f64)
+        });
+        let concurrencies = Self::points(
+            &stages,
+            concurrency.map(|concurrency| concurrency as f64),
+            |stage| stage.concurrency.map(|concurrency| concurrency as f64),
+        );
+
+        Self {
+            stages,
+            rates,
+            concurrencies,
+        }
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.stages.iter().map(|stage| stage.duration).sum()
+    }
+
+    pub fn has_rate(&self) -> bool {
+        self.rates.is_some()
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.concurrencies.as_ref().map(|points| {
+            points
+                .iter()
+                .fold(1f64, |maximum, point| maximum.max(*point))
+                .ceil() as usize
+        })
+    }
+
+    pub fn stage(&self, elapsed: f64) -> Option<usize> {
+        let mut start = 0f64;
+
+        for (index, stage) in self.stages.iter().enumerate() {
+            start += stage.duration as f64;
+
+            if elapsed < start {
+                return Some(index);
+            }
+        }
+
+        None
+    }
+
+    pub fn rate(&self, elapsed: f64) -> Option<f64> {
+        self.rates
+            .as_ref()
+            .map(|points| self.interpolate(points, elapsed))
+    }
+
+    pub fn concurrency(&self, elapsed: f64) -> Option<usize> {
+        self.concurrencies
+            .as_ref()
+            .map(|points| self.interpolate(points, elapsed).ceil() as usize)
+    }
+
+    // Seconds from the start at which the request with the given index is due, found by
+    // integrating the rate over the stages. None once the profile has ended.
+    pub fn offset(&self, index: u64) -> Option<f64> {
+        let points = self.rates.as_ref()?;
+        let mut remaining = index as f64;
+        let mut start = 0f64;
+
+        for (stage, pair) in self.stages.iter().zip(points.windows(2)) {
+            let duration = stage.duration as f64;
+            let (from, to) = (pair[0], pair[1]);
+            let area = (from + to) / 2f64 * duration;
+
+            if remaining < area {
+                let slope = (to - from) / duration;
+
+                let offset = if slope.abs() < f64::EPSILON {
+                    remaining / from
+                } else {
+                    (-from + (from * from + 2f64 * slope * remaining).sqrt()) / slope
+                };
+
+                return Some(start + offset);
+            }
+
+            remaining -= area;
+            start += duration;
+        }
+
+        None
+    }
+
+    pub fn describe(&self, index: usize) -> String {
+        let mut targets = Vec::new();
+
+        if let Some(points) = &self.rates {
+            targets.push(Self::describe_target(points, index, "req/s"));
+        }
+
+        if let Some(points) = &self.concurrencies {
+            targets.push(Self::describe_target(points, index, " workers"));
+        }
+
+        format!(
+            "Stage {}/{}: {}",
+            index + 1,
+            self.stages.len(),
+            targets.join(", ")
+        )
+    }
+
+    fn describe_target(points: &[f64], index: usize, unit: &str) -> String {
+        let (from, to) = (points[index], points[index + 1]);
+
+        if (from - to).abs() < f64::EPSILON {
+            format!("hold {}{}", to, unit)
+        } else {
+            format!("ramp {}→{}{}", from, to, unit)
+        }
+    }
+
+    fn interpolate(&self, points: &[f64], elapsed: f64) -> f64 {
+        let mut start = 0f64;
+
+        for (stage, pair) in self.stages.iter().zip(points.windows(2)) {
+            let duration = stage.duration as f64;
+
+            if elapsed < start + duration {
+                return pair[0] + (pair[1] - pair[0]) * (elapsed - start) / duration;
+            }
+
+            start += duration;
+        }
+
+        points.last().copied().unwrap_or(0f64)
+    }
+
+    fn points(
+        stages: &[Stage],
+        start: Option<f64>,
+        target: impl Fn(&Stage) -> Option<f64>,
+    ) -> Option<Vec<f64>> {
+        let staged = stages.iter().any(|stage| target(stage).is_some());
+
+        if !staged && start.is_none() {
+            return None;
+        }
+
+        let mut points = vec![start.unwrap_or(0f64)];
+
+        for stage in stages {
+            let previous = points[points.len() - 1];
+
+            points.push(target(stage).unwrap_or(previous));
+        }
+
+        Some(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(stages: &[&str], rate: Option<u64>, concurrency: Option<usize>) -> Profile {
+        let stages = stages.iter().map(|stage| stage.parse().unwrap()).collect();
+
+        Profile::new(stages, rate, concurrency)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn interpolates_within_and_after_stages() {
+        let profile = profile(&["10s:rate=100", "10s:rate=50"], None, None);
+        let points = profile.rates.as_ref().unwrap();
+
+        assert_close(profile.interpolate(points, 0f64), 0f64);
+        assert_close(profile.interpolate(points, 5f64), 50f64);
+        assert_close(profile.interpolate(points, 15f64), 75f64);
+        assert_close(profile.interpolate(points, 60f64), 50f64);
+    }
+
+    #[test]
+    fn ramps_from_the_given_start() {
+        let profile = profile(&["10s:concurrency=10", "5s"], Some(20), Some(1));
+
+        assert_eq!(profile.concurrency(5f64), Some(6));
+        assert_eq!(profile.concurrency(12f64), Some(10));
+        assert_eq!(profile.max_concurrency(), Some(10));
+        assert_close(profile.rate(12f64).unwrap(), 20f64);
+    }
+
+    #[test]
+    fn offsets_requests_at_a_held_rate() {
+        let profile = profile(&["10s"], Some(100), None);
+
+        assert_close(profile.offset(0).unwrap(), 0f64);
+        assert_close(profile.offset(250).unwrap(), 2.5f64);
+        assert_close(profile.offset(999).unwrap(), 9.99f64);
+        assert_eq!(profile.offset(1000), None);
+    }
+
+    #[test]
+    fn offsets_requests_along_a_ramp() {
+        let profile = profile(&["10s:rate=100", "10s"], None, None);
+
+        // The first stage sends 500 requests, ramping from none to 100 per second.
+        assert_close(profile.offset(250).unwrap(), 50f64.sqrt());
+        assert_close(profile.offset(600).unwrap(), 11f64);
+        assert_eq!(profile.offset(1500), None);
+    }
+
+    #[test]
+    fn has_no_offsets_without_a_rate() {
+        let profile = profile(&["10s:concurrency=4"], None, None);
+
+        assert!(!profile.has_rate());
+        assert_eq!(profile.rate(5f64), None);
+        assert_eq!(profile.offset(0), None);
+    }
+}
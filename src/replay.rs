@@ -0,0 +1,238 @@
+use crate::feeder::Row;
+use crate::settings::DEFAULT_USER_AGENT;
+use chrono::DateTime;
+use hyper::Method;
+use regex::Regex;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use url::Url;
+
+// Placeholders every replayed request fills in.
+pub const VARIABLES: &[&str] = &["path", "user_agent"];
+
+// Remote host, identity, user, [time], "request line", status and size, followed by the
+// referer and user agent in the combined format.
+const LOG_PATTERN: &str = r#"^\S+ \S+ \S+ \[([^\]]+)\] "((?:[^"\\]|\\.)*)" \S+ \S+(?: "(?:[^"\\]|\\.)*" "((?:[^"\\]|\\.)*)")?"#;
+const TIME_FORMAT: &str = "%d/%b/%Y:%H:%M:%S %z";
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub methods: Vec<Method>,
+    entries: Arc<Vec<Entry>>,
+    speed: Option<f64>,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    // Seconds since the first request of the log.
+    offset: f64,
+    method: usize,
+    path: String,
+    user_agent: Option<String>,
+}
+
+impl Replay {
+    // Lines that are not requests, such as those of clients sending garbage, are skipped.
+    pub fn load(path: &str, speed: Option<f64>) -> Result<Self, String> {
+        let file = File::open(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+        let pattern = Regex::new(LOG_PATTERN).expect("log pattern");
+
+        let mut methods: Vec<Method> = Vec::new();
+        let mut lines = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|error| format!("cannot read {}: {}", path, error))?;
+
+            let captures = match pattern.captures(&line) {
+                Some(captures) => captures,
+                None => continue,
+            };
+
+            let time = match DateTime::parse_from_str(&captures[1], TIME_FORMAT) {
+                Ok(time) => time,
+                Err(_) => continue,
+            };
+
+            let mut request = captures[2].split(' ');
+
+            let (method, path) = match (request.next(), request.next().and_then(target)) {
+                (Some(method), Some(path)) => match method.parse::<Method>() {
+                    Ok(method) => (method, path),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+
+            let method = match methods.iter().position(|known| *known == method) {
+                Some(index) => index,
+                None => {
+                    methods.push(method);
+                    methods.len() - 1
+                }
+            };
+
+            let user_agent = captures
+                .get(3)
+                .map(|user_agent| unescape(user_agent.as_str()))
+                .filter(|user_agent| !user_agent.is_empty() && user_agent != "-");
+
+            lines.push((time, method, path, user_agent));
+        }
+
+        // Servers write a line once the response is sent, so the log is only roughly in
+        // order of arrival.
+        lines.sort_by_key(|(time, ..)| *time);
+
+        let first_time = match lines.first() {
+            Some((time, ..)) => *time,
+            None => return Err(format!("access log {} has no requests", path)),
+        };
+
+        let entries = lines
+            .into_iter()
+            .map(|(time, method, path, user_agent)| Entry {
+                offset: (time - first_time).num_milliseconds() as f64 / 1000f64,
+                method,
+                path,
+                user_agent,
+            })
+            .collect();
+
+        Ok(Self {
+            methods,
+            entries: Arc::new(entries),
+            speed,
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn paced(&self) -> bool {
+        self.speed.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Index of the next request for workers sending them as fast as they can.
+    pub fn next(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Seconds from the start of the run until a request is due at the scaled original pace.
+    pub fn offset(&self, index: usize) -> Option<f64> {
+        let entry = self.entries.get(index)?;
+
+        Some(entry.offset / self.speed.unwrap_or(1f64))
+    }
+
+    // The endpoint of the request method and the placeholders of the request, or None once
+    // the log ran out.
+    pub fn get(&self, index: usize) -> Option<(usize, Row)> {
+        let entry = self.entries.get(index)?;
+
+        let mut row = Row::new();
+
+        row.insert("path".to_string(), entry.path.to_string());
+        row.insert(
+            "user_agent".to_string(),
+            entry
+                .user_agent
+                .as_deref()
+                .unwrap_or(DEFAULT_USER_AGENT)
+                .to_string(),
+        );
+
+        Some((entry.method, row))
+    }
+}
+
+// The path and query of the request target, which proxies log as an absolute URL. Other
+// targets, such as the host and port of a CONNECT request, are not replayed.
+fn target(target: &str) -> Option<String> {
+    if target.starts_with('/') {
+        return Some(target.to_string());
+    }
+
+    let url = target
+        .parse::<Url>()
+        .ok()
+        .filter(|url| url.scheme() == "http" || url.scheme() == "https")?;
+
+    match url.query() {
+        Some(query) => Some(format!("{}?{}", url.path(), query)),
+        None => Some(url.path().to_string()),
+    }
+}
+
+// Quotes and backslashes within a field are escaped in the log.
+fn unescape(value: &str) -> String {
+    value.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captures(line: &str) -> Option<Vec<Option<String>>> {
+        let pattern = Regex::new(LOG_PATTERN).unwrap();
+        let captures = pattern.captures(line)?;
+
+        Some(
+            (1..=3)
+                .map(|index| captures.get(index).map(|value| value.as_str().to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn matches_the_common_format() {
+        let line =
+            r#"10.0.0.1 - - [10/Oct/2026:13:55:36 +0000] "GET /items?page=2 HTTP/1.1" 200 512"#;
+
+        assert_eq!(
+            captures(line),
+            Some(vec![
+                Some("10/Oct/2026:13:55:36 +0000".to_string()),
+                Some("GET /items?page=2 HTTP/1.1".to_string()),
+                None,
+            ])
+        );
+    }
+
+    #[test]
+    fn matches_the_combined_format_with_escaped_quotes() {
+        let line = r#"10.0.0.1 - bob [10/Oct/2026:13:55:36 -0700] "POST /search HTTP/1.1" 201 - "https://example.com/" "Agent \"1.0\"""#;
+
+        let captures = captures(line).unwrap();
+
+        assert_eq!(captures[1].as_deref(), Some("POST /search HTTP/1.1"));
+        assert_eq!(captures[2].as_deref(), Some(r#"Agent \"1.0\""#));
+        assert_eq!(unescape(captures[2].as_deref().unwrap()), r#"Agent "1.0""#);
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_requests() {
+        assert_eq!(captures(""), None);
+        assert_eq!(captures("error: upstream timed out"), None);
+        assert_eq!(
+            captures(r#"10.0.0.1 - - [10/Oct/2026:13:55:36 +0000] "GET /"#),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_the_path_and_query_of_targets() {
+        assert_eq!(target("/a/b?c=1"), Some("/a/b?c=1".to_string()));
+        assert_eq!(
+            target("http://example.com:8080/a?c=1"),
+            Some("/a?c=1".to_string())
+        );
+        assert_eq!(target("https://example.com"), Some("/".to_string()));
+        assert_eq!(target("*"), None);
+        assert_eq!(target("example.com:443"), None);
+    }
+}
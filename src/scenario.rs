@@ -0,0 +1,90 @@
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::time;
+
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub requests: Vec<ScenarioRequest>,
+    #[serde(default)]
+    pub steps: Vec<ScenarioRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScenarioRequest {
+    pub name: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    pub method: String,
+    pub url: String,
+    pub data: Option<String>,
+    pub body_file: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub extract: BTreeMap<String, ScenarioExtract>,
+    #[serde(default)]
+    pub check: ScenarioCheck,
+    // Waited before sending the request, only set when replaying recorded timing.
+    #[serde(skip)]
+    pub delay: Option<time::Duration>,
+    // Imported requests are sent as they were given, so their URL and headers are not
+    // templates and their body is sent as it is instead of data.
+    #[serde(skip)]
+    pub literal: bool,
+    #[serde(skip)]
+    pub body: Option<Bytes>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ScenarioCheck {
+    pub status: Vec<u16>,
+    pub contains: Option<String>,
+    pub regex: Option<String>,
+    pub json: BTreeMap<String, String>,
+    pub headers: Vec<String>,
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScenarioExtract {
+    Json(String),
+    Regex(String),
+    Header(String),
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+pub fn load(path: &str) -> Result<Scenario, String> {
+    let file = File::open(path).map_err(|error| format!("cannot read {}: {}", path, error))?;
+
+    let scenario: Scenario = serde_yaml::from_reader(file)
+        .map_err(|error| format!("invalid scenario {}: {}", path, error))?;
+
+    let mut requests = scenario.requests.iter().chain(&scenario.steps);
+
+    if let Some(request) =
+        requests.find(|request| request.data.is_some() && request.body_file.is_some())
+    {
+        return Err(format!(
+            "scenario {} gives {} both data and body_file",
+            path, request.url
+        ));
+    }
+
+    match (scenario.requests.is_empty(), scenario.steps.is_empty()) {
+        (true, true) => Err(format!("scenario {} has no requests", path)),
+        (false, false) => Err(format!("scenario {} has both requests and steps", path)),
+        // Requests are picked by weight, so at least one has to be picked at all.
+        (false, true) if scenario.requests.iter().all(|request| request.weight == 0) => Err(
+            format!("scenario {} has no request with a weight above zero", path),
+        ),
+        _ => Ok(scenario),
+    }
+}
@@ -1,65 +1,824 @@
-use clap::{value_t, values_t, ArgMatches};
-use reqwest::header::{HeaderMap, HeaderName};
-use reqwest::header::{ACCEPT, ACCEPT_ENCODING, CONTENT_TYPE, HOST, USER_AGENT};
-use reqwest::{Method, Url};
+use crate::body::{self, Body, Field};
+use crate::check::Check;
+use crate::curl::CurlCommand;
+use crate::export::{Format, Output};
+use crate::extract::Extractor;
+use crate::feeder::{self, Feeder, FeederMode};
+use crate::har::{self, HarMode};
+use crate::profile::{Profile, Stage};
+use crate::replay::{self, Replay};
+use crate::scenario::{self, Scenario, ScenarioCheck, ScenarioRequest};
+use crate::template::{Context, Template};
+use crate::threshold::{Metric, Threshold};
+use crate::tls::{self, Tls, TlsVersion};
+use clap::{value_t, value_t_or_exit, values_t, values_t_or_exit, ArgMatches, Error, ErrorKind};
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::header::{ACCEPT, ACCEPT_ENCODING, CONTENT_TYPE, HOST, USER_AGENT};
+use hyper::Method;
+use rand::distributions::WeightedIndex;
+use rand::{thread_rng, Rng};
+use std::io;
+use std::time;
+use url::Url;
+
+pub const DEFAULT_USER_AGENT: &str = "http-storm/0.1.0";
 
 #[derive(Debug, Clone)]
-pub struct Settings {
+pub struct Endpoint {
+    pub name: String,
+    pub weight: u32,
     pub method: Method,
-    pub url: Url,
-    pub data: Option<String>,
+    pub url: Template,
+    pub body: Option<Body>,
     pub headers: HeaderMap,
+    pub header_templates: Vec<(HeaderName, Template)>,
+    pub extractors: Vec<Extractor>,
+    pub checks: Vec<Check>,
+    pub delay: Option<time::Duration>,
+    // A session starts over when this step fails, since a later step uses values it extracts.
+    pub restart: bool,
+}
+
+impl Endpoint {
+    fn templates(&self) -> impl Iterator<Item = &Template> {
+        std::iter::once(&self.url)
+            .chain(self.body.iter().flat_map(|body| body.templates()))
+            .chain(self.header_templates.iter().map(|(_, template)| template))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HttpVersion {
+    Http1,
+    Http2,
+    Http2PriorKnowledge,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub endpoints: Vec<Endpoint>,
+    pub endpoint_weights: WeightedIndex<u32>,
+    pub feeder: Option<Feeder>,
+    pub replay: Option<Replay>,
+    pub session: bool,
 
     pub concurrency: usize,
     pub rate: Option<u64>,
     pub total: Option<u64>,
     pub duration: Option<u64>,
+    pub open_model: bool,
+    pub profile: Option<Profile>,
+    pub connect_timeout: Option<time::Duration>,
+    pub timeout: Option<time::Duration>,
+    pub read_timeout: Option<time::Duration>,
+    pub http_version: HttpVersion,
+    pub shared_client: bool,
+    pub keep_alive: bool,
+    pub max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout: Option<time::Duration>,
+    pub tls: Tls,
+    pub keep_body: bool,
+    pub percentiles: Vec<f64>,
+    pub thresholds: Vec<Threshold>,
+    pub headless: bool,
+    pub output: Option<Output>,
+    pub samples: Option<Output>,
 }
 
 impl Settings {
     pub fn from_matches(matches: ArgMatches) -> Self {
-        let method = value_t!(matches, "method", Method).expect("method");
-        let url = value_t!(matches, "url", Url).expect("url");
-        let data = value_t!(matches, "data", String).ok();
-        let headers = Self::from_matches_headers(&matches, &url);
+        let curl = value_t!(matches, "from-curl", String).ok().map(|command| {
+            CurlCommand::parse(&command)
+                .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+        });
+
+        let insecure = curl.as_ref().is_some_and(|curl| curl.insecure);
+
+        // An imported curl command runs as a scenario with that one request.
+        let scenario = match curl {
+            Some(curl) => Some(Scenario {
+                requests: vec![curl.request],
+                steps: Vec::new(),
+            }),
+            None => match value_t!(matches, "har", String) {
+                Ok(path) => Some(Self::from_matches_har(&matches, &path)),
+                Err(_) => value_t!(matches, "scenario", String).ok().map(|path| {
+                    scenario::load(&path)
+                        .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+                }),
+            },
+        };
+
+        // Scenario steps run in order as a session instead of being picked by weight.
+        let session = scenario
+            .as_ref()
+            .is_some_and(|scenario| !scenario.steps.is_empty());
+
+        let replay = Self::from_matches_replay(&matches);
+
+        let mut endpoints = match &replay {
+            Some(replay) => Self::from_matches_replay_endpoints(&matches, replay),
+            None => Self::from_matches_endpoints(&matches, scenario),
+        };
+        let checks = Self::from_matches_checks(&matches);
+
+        if session {
+            Self::mark_restarts(&mut endpoints);
+        }
+
+        for endpoint in &mut endpoints {
+            endpoint.checks.extend(checks.iter().cloned());
+        }
+
+        // Steps run in order whatever their weights.
+        let weights = endpoints.iter().map(|endpoint| match session {
+            true => 1,
+            false => endpoint.weight,
+        });
+
+        let endpoint_weights = WeightedIndex::new(weights).unwrap_or_else(|_| {
+            Error::value_validation_auto("no request has a weight above zero".to_string()).exit()
+        });
 
         let concurrency = value_t!(matches, "concurrency", usize).expect("concurrency");
         let rate = value_t!(matches, "rate", u64).ok();
         let total = value_t!(matches, "total", u64).ok();
         let duration = value_t!(matches, "duration", u64).ok();
+        // Replaying at the original pace dispatches requests on the schedule of the log.
+        let open_model =
+            matches.is_present("open") || replay.as_ref().is_some_and(|replay| replay.paced());
+        let profile = Self::from_matches_profile(&matches);
+        let connect_timeout = Self::from_matches_timeout(&matches, "connect-timeout");
+        let timeout = Self::from_matches_timeout(&matches, "timeout");
+        let read_timeout = Self::from_matches_timeout(&matches, "read-timeout");
+        let http_version = Self::from_matches_http_version(&matches, &endpoints);
+        let shared_client = value_t!(matches, "client", String).expect("client") == "shared";
+        let keep_alive = !matches.is_present("no-keep-alive");
+        let max_idle_per_host = match matches.is_present("max-idle-per-host") {
+            true => Some(value_t_or_exit!(matches, "max-idle-per-host", usize)),
+            false => None,
+        };
+        let pool_idle_timeout = Self::from_matches_timeout(&matches, "pool-idle-timeout");
+
+        // Sessions keep cookies in the client, which would mix them up between workers.
+        if shared_client && session {
+            Error::with_description(
+                "--client shared cannot run a scenario with steps, each session needs its own client",
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+        let tls = Self::from_matches_tls(&matches, insecure);
+        let keep_body = matches.is_present("keep-body");
+        let thresholds = Self::from_matches_thresholds(&matches);
+        let percentiles = Self::from_matches_percentiles(&matches, &thresholds);
+        // Keys cannot be read once stdin carried the request body.
+        let headless = matches.is_present("headless")
+            || matches.is_present("data-stdin")
+            || !termion::is_tty(&io::stdout());
+
+        let format = value_t!(matches, "format", String)
+            .ok()
+            .map(|format| Format::from_name(&format));
+        let output = value_t!(matches, "output", String)
+            .ok()
+            .map(|path| Self::from_matches_output(path, format));
+        let samples = value_t!(matches, "samples", String)
+            .ok()
+            .map(|path| Self::from_matches_output(path, format));
+
+        // A profile runs for its whole length and a replay until the log runs out, rather
+        // than stopping at the default total.
+        let total = match (&profile, &replay) {
+            (Some(_), _) | (_, Some(_)) if matches.occurrences_of("total") == 0 => None,
+            _ => total,
+        };
+
+        let duration = match &profile {
+            Some(profile) => Some(duration.unwrap_or(u64::MAX).min(profile.duration())),
+            None => duration,
+        };
+
+        let concurrency = match profile
+            .as_ref()
+            .and_then(|profile| profile.max_concurrency())
+        {
+            Some(concurrency) => concurrency,
+            None => concurrency,
+        };
+
+        let has_rate = rate.is_some()
+            || profile.as_ref().is_some_and(|profile| profile.has_rate())
+            || replay.as_ref().is_some_and(|replay| replay.paced());
+
+        if open_model && !has_rate {
+            Error::with_description(
+                "--open requires --rate or a --stage with a rate target",
+                ErrorKind::MissingRequiredArgument,
+            )
+            .exit();
+        }
+
+        // Without stages a fixed schedule of zero requests per second never sends any.
+        if open_model && profile.is_none() && rate == Some(0) {
+            Error::value_validation_auto("--open requires a rate above zero".to_string()).exit();
+        }
+
+        // Every worker sends at least one request, so never spawn more than the total.
+        let concurrency = match total {
+            Some(total) => concurrency.min(total.max(1) as usize),
+            None => concurrency,
+        };
+
+        // Nor more workers than the log has requests to replay.
+        let concurrency = match &replay {
+            Some(replay) => concurrency.min(replay.len()),
+            None => concurrency,
+        };
+
+        let feeder = Self::from_matches_feeder(&matches, concurrency);
+
+        Self::check_variables(&endpoints, feeder.as_ref(), replay.as_ref());
 
         Self {
-            method: method,
-            url: url,
-            data: data,
-            headers: headers,
+            endpoints,
+            endpoint_weights,
+            feeder,
+            replay,
+            session,
+
+            concurrency,
+            rate,
+            total,
+            duration,
+            open_model,
+            profile,
+            connect_timeout,
+            timeout,
+            read_timeout,
+            http_version,
+            shared_client,
+            keep_alive,
+            max_idle_per_host,
+            pool_idle_timeout,
+            tls,
+            keep_body,
+            percentiles,
+            thresholds,
+            headless,
+            output,
+            samples,
+        }
+    }
+
+    pub fn pick_endpoint(&self) -> usize {
+        thread_rng().sample(&self.endpoint_weights)
+    }
+
+    // Checks given on the command line apply to every request.
+    fn from_matches_checks(matches: &ArgMatches) -> Vec<Check> {
+        let json = matches
+            .values_of("check-json")
+            .unwrap_or_default()
+            .map(|value| {
+                let mut pair = value.splitn(2, '=');
+
+                match (pair.next(), pair.next()) {
+                    (Some(path), Some(expected)) => (path.to_string(), expected.to_string()),
+                    _ => {
+                        Error::value_validation_auto(format!("invalid json check {}", value)).exit()
+                    }
+                }
+            })
+            .collect();
+
+        let status = match matches.is_present("check-status") {
+            true => values_t_or_exit!(matches, "check-status", u16),
+            false => Vec::new(),
+        };
+
+        let max_size = match matches.is_present("check-max-size") {
+            true => Some(value_t_or_exit!(matches, "check-max-size", u64)),
+            false => None,
+        };
+
+        let check = ScenarioCheck {
+            status,
+            contains: value_t!(matches, "check-contains", String).ok(),
+            regex: value_t!(matches, "check-regex", String).ok(),
+            json,
+            headers: values_t!(matches, "check-header", String).unwrap_or_default(),
+            max_size,
+        };
+
+        Check::from_scenario_check(&check)
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+    }
+
+    fn from_matches_feeder(matches: &ArgMatches, workers: usize) -> Option<Feeder> {
+        let path = value_t!(matches, "data-file", String).ok()?;
+
+        let rows = feeder::load(&path, Format::from_path(&path))
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit());
+        let mode =
+            FeederMode::from_name(&value_t!(matches, "data-mode", String).expect("data mode"));
+        let wrap = value_t!(matches, "data-exhausted", String).expect("data exhausted") == "wrap";
+
+        Some(Feeder::new(rows, mode, wrap, workers))
+    }
+
+    // Placeholders that are not generators must name a column of the data file or a value
+    // extracted from a response.
+    fn check_variables(endpoints: &[Endpoint], feeder: Option<&Feeder>, replay: Option<&Replay>) {
+        let mut names: Vec<&str> = endpoints
+            .iter()
+            .flat_map(|endpoint| endpoint.extractors.iter())
+            .map(|extractor| extractor.name.as_str())
+            .collect();
+
+        if let Some(feeder) = feeder {
+            names.extend(feeder.columns.iter().map(|column| column.as_str()));
+        }
+
+        if replay.is_some() {
+            names.extend(replay::VARIABLES);
+        }
+
+        for endpoint in endpoints {
+            for template in endpoint.templates() {
+                for name in template.variables() {
+                    if !names.contains(&name) {
+                        Error::value_validation_auto(format!(
+                            "unknown placeholder {{{{{}}}}}",
+                            name
+                        ))
+                        .exit();
+                    }
+                }
+            }
+        }
+    }
+
+    // Steps carry on after a failed one unless a later step needs what it would extract.
+    fn mark_restarts(endpoints: &mut [Endpoint]) {
+        for index in 0..endpoints.len() {
+            let needed = endpoints[index + 1..].iter().any(|later| {
+                later.templates().any(|template| {
+                    template.variables().any(|name| {
+                        endpoints[index]
+                            .extractors
+                            .iter()
+                            .any(|extractor| extractor.name == name)
+                    })
+                })
+            });
+
+            endpoints[index].restart = needed;
+        }
+    }
+
+    fn from_matches_replay(matches: &ArgMatches) -> Option<Replay> {
+        let path = value_t!(matches, "replay", String).ok()?;
+
+        let speed = match matches.is_present("replay-speed") {
+            true => Some(value_t_or_exit!(matches, "replay-speed", f64)),
+            false => None,
+        };
+
+        if speed.is_some_and(|speed| speed <= 0f64) {
+            Error::value_validation_auto("--replay-speed must be above zero".to_string()).exit();
+        }
+
+        let replay = Replay::load(&path, speed)
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit());
+
+        Some(replay)
+    }
+
+    // One endpoint for each request method in the log, on the host requests are sent to.
+    fn from_matches_replay_endpoints(matches: &ArgMatches, replay: &Replay) -> Vec<Endpoint> {
+        let host = value_t!(matches, "replay-host", String).expect("replay host");
+        let url = Self::from_value_url(&format!("{}{{{{path}}}}", host.trim_end_matches('/')));
+
+        replay
+            .methods
+            .iter()
+            .map(|method| {
+                let (headers, mut header_templates) = Self::from_matches_headers(matches, &url);
+
+                header_templates.push((USER_AGENT, Self::from_value_template("{{user_agent}}")));
+
+                Endpoint {
+                    name: method.to_string(),
+                    weight: 1,
+                    method: method.clone(),
+                    url: url.clone(),
+                    body: None,
+                    headers,
+                    header_templates,
+                    extractors: Vec::new(),
+                    checks: Vec::new(),
+                    delay: None,
+                    restart: false,
+                }
+            })
+            .collect()
+    }
+
+    fn from_matches_profile(matches: &ArgMatches) -> Option<Profile> {
+        let stages: Vec<Stage> = matches
+            .values_of("stage")
+            .unwrap_or_default()
+            .map(|value| {
+                value
+                    .parse::<Stage>()
+                    .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+            })
+            .collect();
+
+        if stages.is_empty() {
+            return None;
+        }
+
+        let rate = value_t!(matches, "rate", u64).ok();
+        let concurrency = match matches.occurrences_of("concurrency") {
+            0 => None,
+            _ => value_t!(matches, "concurrency", usize).ok(),
+        };
+
+        Some(Profile::new(stages, rate, concurrency))
+    }
+
+    // Times in ms, s or m, seconds when no unit is given; zero turns the timeout off.
+    fn from_matches_timeout(matches: &ArgMatches, name: &str) -> Option<time::Duration> {
+        let value = value_t!(matches, name, String).ok()?;
 
-            concurrency: concurrency,
-            rate: rate,
-            total: total,
-            duration: duration,
+        let (number, scale) = if let Some(number) = value.strip_suffix("ms") {
+            (number, 1)
+        } else if let Some(number) = value.strip_suffix('s') {
+            (number, 1000)
+        } else if let Some(number) = value.strip_suffix('m') {
+            (number, 60_000)
+        } else {
+            (value.as_str(), 1000)
+        };
+
+        let millis = number.parse::<u64>().unwrap_or_else(|_| {
+            Error::value_validation_auto(format!("invalid {} {}", name, value)).exit()
+        });
+
+        match millis {
+            0 => None,
+            millis => Some(time::Duration::from_millis(millis * scale)),
+        }
+    }
+
+    fn from_matches_http_version(matches: &ArgMatches, endpoints: &[Endpoint]) -> HttpVersion {
+        if matches.is_present("http2-prior-knowledge") {
+            return HttpVersion::Http2PriorKnowledge;
+        }
+
+        if !matches.is_present("http2") {
+            return HttpVersion::Http1;
+        }
+
+        // Without TLS there is nothing to negotiate HTTP/2 with.
+        let cleartext = endpoints.iter().any(|endpoint| {
+            Url::parse(&endpoint.url.render(&Context::default()))
+                .is_ok_and(|url| url.scheme() != "https")
+        });
+
+        if cleartext {
+            Error::with_description(
+                "--http2 requires https URLs, use --http2-prior-knowledge for h2c",
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+
+        HttpVersion::Http2
+    }
+
+    fn from_matches_tls(matches: &ArgMatches, insecure: bool) -> Tls {
+        let roots = match value_t!(matches, "cacert", String) {
+            Ok(path) => tls::load_certificates(&path)
+                .unwrap_or_else(|error| Error::value_validation_auto(error).exit()),
+            Err(_) => Vec::new(),
+        };
+
+        let identity = value_t!(matches, "cert", String).ok().map(|path| {
+            let key = value_t!(matches, "key", String).ok();
+            let password = value_t!(matches, "cert-password", String).ok();
+
+            tls::load_identity(&path, key.as_deref(), password.as_deref())
+                .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+        });
+
+        let version = |name| {
+            value_t!(matches, name, String)
+                .ok()
+                .map(|version| TlsVersion::from_name(&version))
+        };
+
+        Tls {
+            insecure: insecure || matches.is_present("insecure"),
+            roots,
+            identity,
+            min_version: version("tls-min"),
+            max_version: version("tls-max"),
+            server_name: value_t!(matches, "sni", String).ok(),
+        }
+    }
+
+    fn from_matches_output(path: String, format: Option<Format>) -> Output {
+        Output::new(path, format).unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+    }
+
+    fn from_matches_thresholds(matches: &ArgMatches) -> Vec<Threshold> {
+        let values = matches.values_of("threshold").unwrap_or_default();
+
+        values
+            .map(|value| {
+                value
+                    .parse::<Threshold>()
+                    .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+            })
+            .collect()
+    }
+
+    fn from_matches_percentiles(matches: &ArgMatches, thresholds: &[Threshold]) -> Vec<f64> {
+        let mut percentiles = values_t_or_exit!(matches, "percentiles", f64);
+
+        // NaN parses as a number, but no percentile is outside 0 to 100.
+        if let Some(percentile) = percentiles
+            .iter()
+            .find(|percentile| !(0f64..=100f64).contains(*percentile))
+        {
+            Error::value_validation_auto(format!(
+                "invalid percentile {}, expected a number from 0 to 100",
+                percentile
+            ))
+            .exit();
+        }
+
+        // Percentile thresholds need their value computed even when not listed.
+        for threshold in thresholds {
+            if let Metric::Percentile(percentile) = threshold.metric {
+                if !percentiles.contains(&percentile) {
+                    percentiles.push(percentile);
+                }
+            }
+        }
+
+        percentiles.sort_by(|a, b| a.total_cmp(b));
+
+        percentiles
+    }
+
+    fn from_matches_har(matches: &ArgMatches, path: &str) -> Scenario {
+        let mode = value_t!(matches, "har-mode", String)
+            .map(|mode| HarMode::from_name(&mode))
+            .unwrap_or(HarMode::Session);
+        let timing = matches.is_present("har-timing");
+
+        // Requests picked at random have no recorded gap between them to wait for.
+        if timing && mode == HarMode::Weighted {
+            Error::with_description(
+                "--har-timing cannot be used with --har-mode weighted",
+                ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+
+        har::load(path, mode, timing)
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+    }
+
+    fn from_matches_endpoints(matches: &ArgMatches, scenario: Option<Scenario>) -> Vec<Endpoint> {
+        match scenario {
+            Some(scenario) => scenario
+                .requests
+                .into_iter()
+                .chain(scenario.steps)
+                .map(|request| Self::from_scenario_request(matches, request))
+                .collect(),
+            None => vec![Self::from_matches_endpoint(matches)],
+        }
+    }
+
+    fn from_matches_endpoint(matches: &ArgMatches) -> Endpoint {
+        let method = Self::from_value_method(&value_t!(matches, "method", String).expect("method"));
+        let url = Self::from_value_url(&value_t!(matches, "url", String).expect("url"));
+        let body = Self::from_matches_body(matches);
+        let (headers, header_templates) = Self::from_matches_headers(matches, &url);
+
+        Endpoint {
+            name: Self::endpoint_name(&method, &url),
+            weight: 1,
+            method,
+            url,
+            body,
+            headers,
+            header_templates,
+            extractors: Vec::new(),
+            checks: Vec::new(),
+            delay: None,
+            restart: false,
+        }
+    }
+
+    fn from_scenario_request(matches: &ArgMatches, request: ScenarioRequest) -> Endpoint {
+        let method = Self::from_value_method(&request.method);
+        let url = match request.literal {
+            true => Self::check_url(Template::literal(&request.url)),
+            false => Self::from_value_url(&request.url),
+        };
+        let body = match (request.body, &request.body_file, &request.data) {
+            (Some(body), _, _) => Some(Body::Bytes(body)),
+            (None, Some(path), _) => Some(Self::from_value_body_file(path)),
+            (None, None, Some(data)) => Some(Body::Template(Self::from_value_template(data))),
+            (None, None, None) => None,
+        };
+        let (mut headers, mut header_templates) = Self::from_matches_headers(matches, &url);
+
+        for (key, value) in &request.headers {
+            Self::insert_header(
+                &mut headers,
+                &mut header_templates,
+                key,
+                value,
+                request.literal,
+            );
+        }
+
+        let extractors = request
+            .extract
+            .iter()
+            .map(|(name, extract)| {
+                Extractor::new(name, extract)
+                    .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+            })
+            .collect();
+
+        let checks = Check::from_scenario_check(&request.check)
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit());
+
+        Endpoint {
+            name: request
+                .name
+                .unwrap_or_else(|| Self::endpoint_name(&method, &url)),
+            weight: request.weight,
+            method,
+            url,
+            body,
+            headers,
+            header_templates,
+            extractors,
+            checks,
+            delay: request.delay,
+            restart: false,
+        }
+    }
+
+    fn from_matches_body(matches: &ArgMatches) -> Option<Body> {
+        if matches.is_present("data-stdin") {
+            return Some(Body::Bytes(body::read_stdin()));
         }
+
+        if let Some(values) = matches.values_of("field") {
+            let fields = values
+                .map(|value| {
+                    Field::parse(value)
+                        .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+                })
+                .collect();
+
+            return Some(Body::Multipart(fields));
+        }
+
+        if let Ok(path) = value_t!(matches, "body-file", String) {
+            return Some(Self::from_value_body_file(&path));
+        }
+
+        value_t!(matches, "data", String)
+            .ok()
+            .map(|data| Body::Template(Self::from_value_template(&data)))
     }
 
-    fn from_matches_headers(matches: &ArgMatches, url: &Url) -> HeaderMap {
-        let host = url.host().expect("host");
+    // File contents are sent as they are, without placeholders.
+    fn from_value_body_file(path: &str) -> Body {
+        Body::Bytes(
+            body::load(path).unwrap_or_else(|error| Error::value_validation_auto(error).exit()),
+        )
+    }
+
+    // Standard methods are recognized whatever their case and sent in upper case, while any
+    // other token is sent as it is written.
+    fn from_value_method(value: &str) -> Method {
+        let standard = [
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::HEAD,
+            Method::OPTIONS,
+            Method::CONNECT,
+            Method::PATCH,
+            Method::TRACE,
+        ];
+
+        if let Some(method) = standard
+            .iter()
+            .find(|method| method.as_str().eq_ignore_ascii_case(value))
+        {
+            return method.clone();
+        }
+
+        value.parse::<Method>().unwrap_or_else(|_| {
+            Error::value_validation_auto(format!("invalid method {}", value)).exit()
+        })
+    }
 
+    fn from_value_template(value: &str) -> Template {
+        value
+            .parse::<Template>()
+            .unwrap_or_else(|error| Error::value_validation_auto(error).exit())
+    }
+
+    fn from_value_url(value: &str) -> Template {
+        Self::check_url(Self::from_value_template(value))
+    }
+
+    // Placeholders are checked by rendering a sample, so a bad URL fails before the run.
+    fn check_url(url: Template) -> Template {
+        if let Err(error) = url.render(&Context::default()).parse::<Url>() {
+            Error::value_validation_auto(format!("invalid url {}: {}", url, error)).exit();
+        }
+
+        url
+    }
+
+    fn endpoint_name(method: &Method, url: &Template) -> String {
+        let path = url.as_str().splitn(4, '/').nth(3).unwrap_or_default();
+        let path = path.split('?').next().unwrap_or_default();
+
+        format!("{} /{}", method, path)
+    }
+
+    fn insert_header(
+        headers: &mut HeaderMap,
+        header_templates: &mut Vec<(HeaderName, Template)>,
+        key: &str,
+        value: &str,
+        literal: bool,
+    ) {
+        let name = key.parse::<HeaderName>().unwrap_or_else(|_| {
+            Error::value_validation_auto(format!("invalid header name {}", key)).exit()
+        });
+        let template = match literal {
+            true => Template::literal(value),
+            false => Self::from_value_template(value),
+        };
+
+        if template.is_static() {
+            let value = value.parse().unwrap_or_else(|_| {
+                Error::value_validation_auto(format!("invalid value of header {}", key)).exit()
+            });
+
+            headers.insert(name, value);
+        } else {
+            header_templates.push((name, template));
+        }
+    }
+
+    fn from_matches_headers(
+        matches: &ArgMatches,
+        url: &Template,
+    ) -> (HeaderMap, Vec<(HeaderName, Template)>) {
         let mut headers = HeaderMap::new();
+        let mut header_templates = Vec::new();
 
         // Default headers
         headers.insert(ACCEPT, "*/*".parse().expect("header"));
         headers.insert(ACCEPT_ENCODING, "gzip, deflate".parse().expect("header"));
-        headers.insert(USER_AGENT, "http-storm/0.1.0".parse().expect("header"));
-        headers.insert(HOST, host.to_string().parse().expect("host"));
+        headers.insert(USER_AGENT, DEFAULT_USER_AGENT.parse().expect("header"));
+
+        // A templated URL may change host per request, so leave Host to the client then.
+        if url.is_static() {
+            let url = url.as_str().parse::<Url>().expect("url");
+            let host = url.host().expect("host");
+
+            headers.insert(HOST, host.to_string().parse().expect("host"));
+        }
 
         Self::from_matches_headers_json(matches, &mut headers);
         Self::from_matches_headers_form(matches, &mut headers);
-        Self::from_matches_headers_custom(matches, &mut headers);
+        Self::from_matches_headers_custom(matches, &mut headers, &mut header_templates);
 
-        headers
+        (headers, header_templates)
     }
 
-    fn from_matches_headers_json(matches: &ArgMatches, headers: &mut HeaderMap) -> () {
+    fn from_matches_headers_json(matches: &ArgMatches, headers: &mut HeaderMap) {
         let json = matches.is_present("json");
         let accept = "application/json, */*";
         let content_type = "application/json";
@@ -70,7 +829,7 @@ impl Settings {
         }
     }
 
-    fn from_matches_headers_form(matches: &ArgMatches, headers: &mut HeaderMap) -> () {
+    fn from_matches_headers_form(matches: &ArgMatches, headers: &mut HeaderMap) {
         let form = matches.is_present("form");
         let content_type = "application/x-www-form-urlencoded; charset=utf-8";
 
@@ -79,21 +838,22 @@ impl Settings {
         }
     }
 
-    fn from_matches_headers_custom(matches: &ArgMatches, headers: &mut HeaderMap) -> () {
+    fn from_matches_headers_custom(
+        matches: &ArgMatches,
+        headers: &mut HeaderMap,
+        header_templates: &mut Vec<(HeaderName, Template)>,
+    ) {
         let header = values_t!(matches, "header", String).ok();
 
-        header.map(|values| {
+        if let Some(values) = header {
             for pair in values.chunks(2) {
                 match pair {
                     [key, value] => {
-                        let name = key.parse::<HeaderName>().expect("header");
-                        let value = value.parse().expect("header");
-
-                        headers.insert(name, value);
+                        Self::insert_header(headers, header_templates, key, value, false)
                     }
                     _ => unreachable!(),
                 }
             }
-        });
+        }
     }
 }
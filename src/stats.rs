@@ -1,119 +1,437 @@
-use crate::worker::WorkerMessage;
-use average::{concatenate, define_histogram, Estimate, Max, Min, Variance};
+use crate::error::ErrorKind;
+use crate::metric::RequestMetric;
+use chrono::Duration;
+use hdrhistogram::Histogram;
+use serde::Serialize;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Stats {
     pub count: u64,
+    pub error_count: u64,
     pub status: Vec<(String, u64)>,
 
-    pub time_values: Vec<u64>,
     pub time_minimum: u64,
     pub time_mean: u64,
     pub time_maximum: u64,
     pub time_stddev: u64,
+    pub time_percentiles: Vec<(f64, u64)>,
     pub time_histogram: Vec<((u64, u64), u64)>,
+
+    pub corrected_count: u64,
+    pub corrected_minimum: u64,
+    pub corrected_mean: u64,
+    pub corrected_maximum: u64,
+    pub corrected_stddev: u64,
+    pub corrected_percentiles: Vec<(f64, u64)>,
+
+    pub phases: Vec<PhaseStats>,
+
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub size_minimum: u64,
+    pub size_mean: u64,
+    pub size_maximum: u64,
+    pub size_percentiles: Vec<(f64, u64)>,
+
+    pub errors: Vec<ErrorStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhaseStats {
+    pub key: String,
+    pub name: String,
+    pub count: u64,
+    pub minimum: u64,
+    pub mean: u64,
+    pub maximum: u64,
+    pub percentiles: Vec<(f64, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorStats {
+    pub kind: String,
+    pub message: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    count: u64,
+    error_count: u64,
+    status: HashMap<String, u64>,
+    time: Histogram<u64>,
+    corrected: Histogram<u64>,
+    dns: Histogram<u64>,
+    connect: Histogram<u64>,
+    tls: Histogram<u64>,
+    ttfb: Histogram<u64>,
+    body: Histogram<u64>,
+    request_bytes: u64,
+    response_bytes: u64,
+    size: Histogram<u64>,
+    errors: HashMap<(ErrorKind, String), u64>,
 }
 
-concatenate!(
-    Estimator,
-    [Min, min, min],
-    [Max, max, max],
-    [Variance, variance, mean, population_variance]
-);
+// Latencies are recorded in microseconds and response sizes in bytes, up to one hour or
+// 3.6GB. Histograms only grow as far as the largest value recorded, since one is kept for
+// every endpoint and protocol. Phases and sizes are only broken down, so 2 significant
+// digits are enough for them.
+const HISTOGRAM_MAXIMUM: u64 = 3_600_000_000;
+const HISTOGRAM_PRECISION: u8 = 3;
+const HISTOGRAM_PHASE_PRECISION: u8 = 2;
+const HISTOGRAM_BUCKETS: u64 = 10;
+
+// Distinct error messages kept per aggregate, and how many of the most frequent are reported.
+const ERRORS_MAXIMUM: usize = 256;
+const ERRORS_TOP: usize = 10;
+
+impl Aggregate {
+    pub fn new() -> Self {
+        Self {
+            count: 0,
+            error_count: 0,
+            status: HashMap::new(),
+            time: histogram(HISTOGRAM_PRECISION),
+            corrected: histogram(HISTOGRAM_PRECISION),
+            dns: histogram(HISTOGRAM_PHASE_PRECISION),
+            connect: histogram(HISTOGRAM_PHASE_PRECISION),
+            tls: histogram(HISTOGRAM_PHASE_PRECISION),
+            ttfb: histogram(HISTOGRAM_PHASE_PRECISION),
+            body: histogram(HISTOGRAM_PHASE_PRECISION),
+            request_bytes: 0,
+            response_bytes: 0,
+            size: histogram(HISTOGRAM_PHASE_PRECISION),
+            errors: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, metric: &RequestMetric) {
+        // Failed requests and checks are counted by their kind rather than a status code.
+        let status = match (&metric.error_kind, &metric.status_code) {
+            (Some(kind), _) => kind.to_string(),
+            (None, Some(status_code)) => status_code.to_string(),
+            (None, None) => ErrorKind::Other.to_string(),
+        };
+
+        self.count += 1;
 
-define_histogram!(hist, 10);
+        if metric.is_error() {
+            self.error_count += 1;
+        }
 
-pub fn compute(messages: &[WorkerMessage]) -> Stats {
-    let count = count(messages);
-    let status = status(messages);
+        *self.status.entry(status).or_insert(0) += 1;
 
-    let time_values = time_values(messages);
-    let estimator = estimator(&time_values);
+        if let Some(kind) = metric.error_kind {
+            let message = metric
+                .error_message
+                .as_ref()
+                .or(metric.check_error.as_ref())
+                .map(|message| message.to_string())
+                .unwrap_or_default();
 
-    let time_minimum = time_minimum(&estimator);
-    let time_mean = time_mean(&estimator);
-    let time_maximum = time_maximum(&estimator);
-    let time_stddev = time_stddev(&estimator);
+            self.record_error(kind, message, 1);
+        }
 
-    let time_histogram = time_histogram(&time_values, time_minimum, time_maximum);
+        record(&mut self.time, micros(metric.elapsed_time));
+
+        // Connection setup is only timed for requests that opened a connection.
+        for (histogram, time) in [
+            (&mut self.dns, metric.dns_time),
+            (&mut self.connect, metric.connect_time),
+            (&mut self.tls, metric.tls_time),
+        ] {
+            if let Some(time) = time {
+                record(histogram, micros(time));
+            }
+        }
+
+        record(&mut self.ttfb, micros(metric.ttfb_time));
+        record(&mut self.body, micros(metric.body_time));
+
+        self.request_bytes += metric.request_bytes;
+        self.response_bytes += metric.response_bytes;
+        record(&mut self.size, metric.response_bytes);
+
+        if metric.intended_time.is_some() {
+            record(&mut self.corrected, micros(metric.response_time()));
+        }
+    }
+
+    pub fn merge(&mut self, other: &Aggregate) {
+        self.count += other.count;
+        self.error_count += other.error_count;
+
+        for (status, count) in &other.status {
+            *self.status.entry(status.to_string()).or_insert(0) += count;
+        }
+
+        self.time.add(&other.time).expect("histogram bounds");
+        self.corrected
+            .add(&other.corrected)
+            .expect("histogram bounds");
+        self.dns.add(&other.dns).expect("histogram bounds");
+        self.connect.add(&other.connect).expect("histogram bounds");
+        self.tls.add(&other.tls).expect("histogram bounds");
+        self.ttfb.add(&other.ttfb).expect("histogram bounds");
+        self.body.add(&other.body).expect("histogram bounds");
+
+        self.request_bytes += other.request_bytes;
+        self.response_bytes += other.response_bytes;
+        self.size.add(&other.size).expect("histogram bounds");
+
+        for ((kind, message), count) in &other.errors {
+            self.record_error(*kind, message.to_string(), *count);
+        }
+    }
+
+    // New messages past the limit are dropped, their kind is still counted in the status.
+    fn record_error(&mut self, kind: ErrorKind, message: String, count: u64) {
+        let key = (kind, message);
+
+        if let Some(total) = self.errors.get_mut(&key) {
+            *total += count;
+        } else if self.errors.len() < ERRORS_MAXIMUM {
+            self.errors.insert(key, count);
+        }
+    }
+}
+
+pub fn compute(aggregate: &Aggregate, percentiles: &[f64]) -> Stats {
+    let count = aggregate.count;
+    let error_count = aggregate.error_count;
+    let status = status(aggregate);
+    let errors = errors(aggregate);
+
+    let request_bytes = aggregate.request_bytes;
+    let response_bytes = aggregate.response_bytes;
+
+    let size = &aggregate.size;
+
+    let size_minimum = size.min();
+    let size_mean = size.mean().floor() as u64;
+    let size_maximum = size.max();
+    let size_percentiles = time_percentiles(size, percentiles);
+
+    let corrected = &aggregate.corrected;
+
+    let corrected_count = corrected.len();
+    let corrected_minimum = time_minimum(corrected);
+    let corrected_mean = time_mean(corrected);
+    let corrected_maximum = time_maximum(corrected);
+    let corrected_stddev = time_stddev(corrected);
+    let corrected_percentiles = time_percentiles(corrected, percentiles);
+
+    let time = &aggregate.time;
+
+    let time_minimum = time_minimum(time);
+    let time_mean = time_mean(time);
+    let time_maximum = time_maximum(time);
+    let time_stddev = time_stddev(time);
+    let time_percentiles = time_percentiles(time, percentiles);
+
+    let time_histogram = time_histogram(time, time_minimum, time_maximum);
+
+    // Setup phases of requests that reused a connection are left out rather than counted as
+    // zero, so each phase has its own count.
+    let phases = vec![
+        phase("dns", "DNS Lookup", &aggregate.dns, percentiles),
+        phase("connect", "TCP Connect", &aggregate.connect, percentiles),
+        phase("tls", "TLS Handshake", &aggregate.tls, percentiles),
+        phase("ttfb", "Time to First Byte", &aggregate.ttfb, percentiles),
+        phase("body", "Body Download", &aggregate.body, percentiles),
+    ];
 
     Stats {
         count,
+        error_count,
         status,
 
-        time_values,
         time_minimum,
         time_mean,
         time_maximum,
         time_stddev,
+        time_percentiles,
         time_histogram,
+
+        corrected_count,
+        corrected_minimum,
+        corrected_mean,
+        corrected_maximum,
+        corrected_stddev,
+        corrected_percentiles,
+
+        phases,
+
+        request_bytes,
+        response_bytes,
+        size_minimum,
+        size_mean,
+        size_maximum,
+        size_percentiles,
+
+        errors,
     }
 }
 
-fn count(messages: &[WorkerMessage]) -> u64 {
-    messages.len() as u64
+pub fn micros(duration: Duration) -> u64 {
+    duration.num_microseconds().unwrap_or(i64::MAX).max(0) as u64
 }
 
-fn status(messages: &[WorkerMessage]) -> Vec<(String, u64)> {
-    let mut map = HashMap::<String, u64>::new();
+pub fn format_phase(phase: &PhaseStats) -> String {
+    let mut line = format!("{}: Mean {}", phase.name, format_time(phase.mean));
 
-    for message in messages {
-        let status = message
-            .metric
-            .status_code
-            .as_ref()
-            .map(|status_code| status_code.to_string())
-            .unwrap_or("Failed".to_string());
+    for (percentile, value) in &phase.percentiles {
+        line.push_str(&format!(" / p{} {}", percentile, format_time(*value)));
+    }
+
+    line.push_str(&format!(" / Slowest {}", format_time(phase.maximum)));
 
-        let count = map.get(&status).map(|value| *value).unwrap_or(0u64);
+    line
+}
 
-        map.insert(status, count + 1);
+pub fn format_protocol(version: &str, stats: &Stats) -> String {
+    format!(
+        "{}: {} requests / {} errors / Mean {} / Slowest {}",
+        version,
+        stats.count,
+        stats.error_count,
+        format_time(stats.time_mean),
+        format_time(stats.time_maximum)
+    )
+}
+
+pub fn format_size(stats: &Stats) -> String {
+    let mut line = format!(
+        "Response Size: Mean {}",
+        format_bytes(stats.size_mean as f64)
+    );
+
+    for (percentile, value) in &stats.size_percentiles {
+        line.push_str(&format!(
+            " / p{} {}",
+            percentile,
+            format_bytes(*value as f64)
+        ));
     }
 
-    let mut vec: Vec<(String, u64)> = map.into_iter().collect();
+    line.push_str(&format!(
+        " / Largest {}",
+        format_bytes(stats.size_maximum as f64)
+    ));
+
+    line
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+
+    while value >= 1024f64 && unit < units.len() - 1 {
+        value /= 1024f64;
+        unit += 1;
+    }
+
+    format!("{:.2}{}", value, units[unit])
+}
+
+pub fn format_time(micros: u64) -> String {
+    format!("{:.2}ms", micros as f64 / 1000f64)
+}
+
+fn histogram(precision: u8) -> Histogram<u64> {
+    Histogram::new(precision).expect("histogram bounds")
+}
+
+fn record(histogram: &mut Histogram<u64>, value: u64) {
+    histogram
+        .record(value.min(HISTOGRAM_MAXIMUM))
+        .expect("histogram bounds");
+}
+
+fn status(aggregate: &Aggregate) -> Vec<(String, u64)> {
+    let mut vec: Vec<(String, u64)> = aggregate
+        .status
+        .iter()
+        .map(|(status, count)| (status.to_string(), *count))
+        .collect();
 
     vec.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
 
     vec
 }
 
-fn time_values(messages: &[WorkerMessage]) -> Vec<u64> {
-    messages
+fn errors(aggregate: &Aggregate) -> Vec<ErrorStats> {
+    let mut vec: Vec<ErrorStats> = aggregate
+        .errors
         .iter()
-        .map(|message| message.metric.elapsed_time.num_milliseconds() as u64)
-        .collect()
-}
+        .map(|((kind, message), count)| ErrorStats {
+            kind: kind.to_string(),
+            message: message.to_string(),
+            count: *count,
+        })
+        .collect();
+
+    vec.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.message.cmp(&b.message))
+    });
+    vec.truncate(ERRORS_TOP);
 
-fn estimator(values: &[u64]) -> Estimator {
-    values.iter().map(|value| *value as f64).collect()
+    vec
 }
 
-fn time_histogram(values: &[u64], min: u64, max: u64) -> Vec<((u64, u64), u64)> {
-    let mut histogram = hist::Histogram::with_const_width(min as f64, max as f64 + 1f64);
+fn time_histogram(histogram: &Histogram<u64>, min: u64, max: u64) -> Vec<((u64, u64), u64)> {
+    let width = ((max - min) / HISTOGRAM_BUCKETS + 1).max(1);
+
+    let mut buckets: Vec<((u64, u64), u64)> = (0..HISTOGRAM_BUCKETS)
+        .map(|index| ((min + index * width, min + (index + 1) * width), 0))
+        .collect();
+
+    for value in histogram.iter_recorded() {
+        let index = (value.value_iterated_to().saturating_sub(min) / width) as usize;
+        let index = index.min(buckets.len() - 1);
 
-    for value in values {
-        histogram.add(*value as f64).expect("histogram bounds");
+        buckets[index].1 += value.count_at_value();
     }
 
-    histogram
-        .iter()
-        .map(|((lower, upper), count)| ((lower.floor() as u64, upper.floor() as u64), count as u64))
-        .collect()
+    buckets
+}
+
+fn phase(key: &str, name: &str, histogram: &Histogram<u64>, percentiles: &[f64]) -> PhaseStats {
+    PhaseStats {
+        key: key.to_string(),
+        name: name.to_string(),
+        count: histogram.len(),
+        minimum: time_minimum(histogram),
+        mean: time_mean(histogram),
+        maximum: time_maximum(histogram),
+        percentiles: time_percentiles(histogram, percentiles),
+    }
+}
+
+fn time_minimum(histogram: &Histogram<u64>) -> u64 {
+    histogram.min()
 }
 
-fn time_minimum(estimator: &Estimator) -> u64 {
-    estimator.min().floor() as u64
+fn time_mean(histogram: &Histogram<u64>) -> u64 {
+    histogram.mean().floor() as u64
 }
 
-fn time_mean(estimator: &Estimator) -> u64 {
-    estimator.mean().floor() as u64
+fn time_maximum(histogram: &Histogram<u64>) -> u64 {
+    histogram.max()
 }
 
-fn time_maximum(estimator: &Estimator) -> u64 {
-    estimator.max().floor() as u64
+fn time_stddev(histogram: &Histogram<u64>) -> u64 {
+    histogram.stdev().floor() as u64
 }
 
-fn time_stddev(estimator: &Estimator) -> u64 {
-    estimator.population_variance().sqrt().floor() as u64
+fn time_percentiles(histogram: &Histogram<u64>, percentiles: &[f64]) -> Vec<(f64, u64)> {
+    percentiles
+        .iter()
+        .map(|percentile| (*percentile, histogram.value_at_percentile(*percentile)))
+        .collect()
 }
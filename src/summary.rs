@@ -1,61 +1,368 @@
+use crate::export::SampleWriter;
 use crate::settings::Settings;
-use crate::stats::{self, Stats};
+use crate::stats::{
+    self, format_bytes, format_phase, format_protocol, format_size, format_time, Aggregate, Stats,
+};
+use crate::threshold::ThresholdResult;
+use crate::tls;
 use crate::worker::WorkerMessage;
+use chrono::Duration;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::UnboundedReceiver;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Summary {
     pub request_method: String,
     pub request_url: String,
+    pub tls_version: Option<String>,
 
     pub elapsed_seconds: u64,
     pub total_count: u64,
+    pub delayed_count: Option<u64>,
     pub progress_percent: u16,
+    pub rate: f64,
+    pub throughput: f64,
+    pub connections_opened: u64,
+    pub connections_reused: u64,
 
+    #[serde(skip)]
+    pub stage: Option<String>,
+    #[serde(skip)]
+    pub timeline: Vec<u64>,
     pub stats: Stats,
+    pub endpoints: Vec<(String, Stats)>,
+    pub protocols: Vec<(String, Stats)>,
+    pub thresholds: Vec<ThresholdResult>,
+}
+
+#[derive(Debug)]
+pub struct Collector {
+    workers: usize,
+    finished_workers: usize,
+    elapsed_time: Duration,
+    total_count: u64,
+    delayed_count: u64,
+    connections_opened: u64,
+    connections_reused: u64,
+    timeline: VecDeque<u64>,
+    endpoints: Vec<Aggregate>,
+    protocols: BTreeMap<String, Aggregate>,
+    names: Vec<String>,
+    samples: Option<SampleWriter>,
+}
+
+// Recent response times kept for the timeline, enough to fill a wide terminal.
+const TIMELINE_CAPACITY: usize = 1024;
+
+impl Collector {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            workers: settings.concurrency,
+            finished_workers: 0,
+            elapsed_time: Duration::zero(),
+            total_count: 0,
+            delayed_count: 0,
+            connections_opened: 0,
+            connections_reused: 0,
+            timeline: VecDeque::with_capacity(TIMELINE_CAPACITY),
+            endpoints: vec![Aggregate::new(); settings.endpoints.len()],
+            protocols: BTreeMap::new(),
+            names: settings
+                .endpoints
+                .iter()
+                .map(|endpoint| endpoint.name.to_string())
+                .collect(),
+            samples: settings.samples.as_ref().map(SampleWriter::create),
+        }
+    }
+
+    // Records the messages waiting in the channel without blocking.
+    pub fn drain(&mut self, receiver: &mut UnboundedReceiver<WorkerMessage>) {
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => {
+                    if message.finished {
+                        self.finished_workers += 1;
+                    }
+
+                    self.record(&message);
+                }
+                // Workers idled by a profile finish without a final message.
+                Err(TryRecvError::Closed) => {
+                    self.finished_workers = self.workers;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished_workers == self.workers
+    }
+
+    fn record(&mut self, message: &WorkerMessage) {
+        self.elapsed_time = self.elapsed_time.max(message.elapsed_time);
+        self.total_count += 1;
+
+        if message.delayed {
+            self.delayed_count += 1;
+        }
+
+        self.connections_opened += message.metric.connections_opened;
+        self.connections_reused += message.metric.connections_reused;
+
+        if self.timeline.len() == TIMELINE_CAPACITY {
+            self.timeline.pop_front();
+        }
+
+        self.timeline
+            .push_back(stats::micros(message.metric.elapsed_time));
+
+        self.endpoints[message.metric.endpoint].record(&message.metric);
+
+        // Requests that failed before a response have no protocol to count them under.
+        if let Some(version) = &message.metric.version {
+            self.protocols
+                .entry(version.to_string())
+                .or_insert_with(Aggregate::new)
+                .record(&message.metric);
+        }
+
+        if let Some(samples) = &mut self.samples {
+            samples.write(message, &self.names[message.metric.endpoint]);
+        }
+    }
+}
+
+impl Summary {
+    pub fn passed(&self) -> bool {
+        self.thresholds.iter().all(|threshold| threshold.passed)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.request_method, self.request_url)?;
+
+        if let Some(tls_version) = &self.tls_version {
+            writeln!(f, "TLS: {}", tls_version)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Requests: {}", self.total_count)?;
+
+        if let Some(delayed_count) = self.delayed_count {
+            writeln!(f, "Delayed: {}", delayed_count)?;
+        }
+
+        writeln!(f, "Elapsed: {}s", self.elapsed_seconds)?;
+        writeln!(f, "Rate: {:.2}req/s", self.rate)?;
+        writeln!(f, "Errors: {}", self.stats.error_count)?;
+        writeln!(
+            f,
+            "Received: {} ({}/s)",
+            format_bytes(self.stats.response_bytes as f64),
+            format_bytes(self.throughput)
+        )?;
+        writeln!(f, "Sent: {}", format_bytes(self.stats.request_bytes as f64))?;
+        writeln!(
+            f,
+            "Connections: {} new / {} reused",
+            self.connections_opened, self.connections_reused
+        )?;
+        writeln!(f)?;
+
+        write_stats(f, &self.stats)?;
+
+        if !self.protocols.is_empty() {
+            writeln!(f)?;
+
+            for (version, stats) in &self.protocols {
+                writeln!(f, "Protocol {}", format_protocol(version, stats))?;
+            }
+        }
+
+        if !self.stats.errors.is_empty() {
+            writeln!(f)?;
+
+            for error in &self.stats.errors {
+                writeln!(
+                    f,
+                    "Error {} ({}): {}",
+                    error.kind, error.count, error.message
+                )?;
+            }
+        }
+
+        if !self.thresholds.is_empty() {
+            writeln!(f)?;
+
+            for threshold in &self.thresholds {
+                let result = if threshold.passed { "PASS" } else { "FAIL" };
+
+                writeln!(
+                    f,
+                    "Threshold {}: {} ({})",
+                    threshold.expression, result, threshold.actual
+                )?;
+            }
+        }
+
+        if self.endpoints.len() > 1 {
+            for (name, stats) in &self.endpoints {
+                writeln!(f)?;
+                writeln!(f, "Endpoint {}", name)?;
+                writeln!(f, "Requests: {}", stats.count)?;
+                write_stats(f, stats)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_stats(f: &mut fmt::Formatter<'_>, stats: &Stats) -> fmt::Result {
+    writeln!(f, "Fastest: {}", format_time(stats.time_minimum))?;
+    writeln!(f, "Slowest: {}", format_time(stats.time_maximum))?;
+    writeln!(f, "Mean: {}", format_time(stats.time_mean))?;
+    writeln!(f, "Standard Deviation: {}", format_time(stats.time_stddev))?;
+
+    for (percentile, value) in &stats.time_percentiles {
+        writeln!(f, "p{}: {}", percentile, format_time(*value))?;
+    }
+
+    writeln!(f)?;
+
+    for phase in stats.phases.iter().filter(|phase| phase.count > 0) {
+        writeln!(f, "{}", format_phase(phase))?;
+    }
+
+    writeln!(f, "{}", format_size(stats))?;
+
+    if stats.corrected_count > 0 {
+        writeln!(f)?;
+        writeln!(f, "Response Time (from intended start)")?;
+        writeln!(f, "Fastest: {}", format_time(stats.corrected_minimum))?;
+        writeln!(f, "Slowest: {}", format_time(stats.corrected_maximum))?;
+        writeln!(f, "Mean: {}", format_time(stats.corrected_mean))?;
+        writeln!(
+            f,
+            "Standard Deviation: {}",
+            format_time(stats.corrected_stddev)
+        )?;
+
+        for (percentile, value) in &stats.corrected_percentiles {
+            writeln!(f, "p{}: {}", percentile, format_time(*value))?;
+        }
+    }
+
+    writeln!(f)?;
+
+    for ((lower, upper), count) in &stats.time_histogram {
+        writeln!(
+            f,
+            "{} ~ {}: {}",
+            format_time(*lower),
+            format_time(*upper),
+            count
+        )?;
+    }
+
+    writeln!(f)?;
+
+    for (status, count) in &stats.status {
+        writeln!(f, "Status {}: {}", status, count)?;
+    }
+
+    Ok(())
 }
 
-pub fn compute(messages: &[WorkerMessage], settings: &Settings, take: usize) -> Summary {
+pub fn compute(collector: &Collector, settings: &Settings, take: usize) -> Summary {
     let request_method = request_method(settings);
     let request_url = request_url(settings);
+    let tls_version = tls::version();
 
-    let elapsed_seconds = elapsed_seconds(messages);
-    let total_count = total_count(messages);
+    let elapsed_seconds = elapsed_seconds(collector);
+    let total_count = collector.total_count;
+    let delayed_count = delayed_count(collector, settings);
     let progress_percent = progress_percent(settings, elapsed_seconds, total_count);
+    let rate = per_second(collector.elapsed_time, total_count);
 
-    let stats = stats(messages, take);
+    let stage = stage(collector, settings);
+    let timeline = timeline(collector, take);
+    let stats = stats(collector, settings);
+    let throughput = per_second(collector.elapsed_time, stats.response_bytes);
+    let connections_opened = collector.connections_opened;
+    let connections_reused = collector.connections_reused;
+    let endpoints = endpoints(collector, settings);
+    let protocols = protocols(collector, settings);
 
-    Summary {
+    let mut summary = Summary {
         request_method,
         request_url,
+        tls_version,
 
         elapsed_seconds,
         total_count,
+        delayed_count,
         progress_percent,
+        rate,
+        throughput,
+        connections_opened,
+        connections_reused,
 
+        stage,
+        timeline,
         stats,
-    }
+        endpoints,
+        protocols,
+        thresholds: Vec::new(),
+    };
+
+    summary.thresholds = thresholds(&summary, settings);
+
+    summary
 }
 
 fn request_method(settings: &Settings) -> String {
-    settings.method.to_string()
+    let mut methods: Vec<String> = settings
+        .endpoints
+        .iter()
+        .map(|endpoint| endpoint.method.to_string())
+        .collect();
+
+    methods.sort();
+    methods.dedup();
+
+    methods.join("/")
 }
 
 fn request_url(settings: &Settings) -> String {
-    settings.url.to_string()
+    match settings.endpoints.as_slice() {
+        [endpoint] => endpoint.url.to_string(),
+        endpoints if settings.session => format!("{} steps", endpoints.len()),
+        endpoints => format!("{} endpoints", endpoints.len()),
+    }
 }
 
-fn elapsed_seconds(messages: &[WorkerMessage]) -> u64 {
-    let last_message = messages.iter().rev().find(|message| message.id == 1);
-
-    if let Some(message) = last_message {
-        (message.elapsed_time.num_seconds() as u64).max(1u64)
+fn elapsed_seconds(collector: &Collector) -> u64 {
+    if collector.total_count > 0 {
+        (collector.elapsed_time.num_seconds() as u64).max(1u64)
     } else {
         0
     }
 }
 
-fn total_count(messages: &[WorkerMessage]) -> u64 {
-    messages.len() as u64
+fn delayed_count(collector: &Collector, settings: &Settings) -> Option<u64> {
+    if settings.open_model {
+        Some(collector.delayed_count)
+    } else {
+        None
+    }
 }
 
 fn progress_percent(settings: &Settings, elapsed_seconds: u64, total_count: u64) -> u16 {
@@ -74,11 +381,74 @@ fn progress_percent(settings: &Settings, elapsed_seconds: u64, total_count: u64)
     (ratio * 100f64).ceil().min(100f64) as u16
 }
 
-fn stats(messages: &[WorkerMessage], take: usize) -> Stats {
-    let from = messages.len() as i64 - take as i64;
-    let from = from.max(0) as usize;
+fn thresholds(summary: &Summary, settings: &Settings) -> Vec<ThresholdResult> {
+    settings
+        .thresholds
+        .iter()
+        .map(|threshold| threshold.evaluate(summary))
+        .collect()
+}
+
+// Measured in milliseconds, since whole seconds overstate the rate of short runs.
+fn per_second(elapsed_time: Duration, total: u64) -> f64 {
+    let millis = elapsed_time.num_milliseconds();
+
+    if millis > 0 {
+        total as f64 * 1000f64 / millis as f64
+    } else {
+        0f64
+    }
+}
+
+// Stages are timed from each worker's start, the same clock as the elapsed time.
+fn stage(collector: &Collector, settings: &Settings) -> Option<String> {
+    let profile = settings.profile.as_ref()?;
+
+    let elapsed = collector.elapsed_time.num_milliseconds() as f64 / 1000f64;
+    let index = profile.stage(elapsed).unwrap_or(profile.stages.len() - 1);
+
+    Some(profile.describe(index))
+}
+
+fn timeline(collector: &Collector, take: usize) -> Vec<u64> {
+    let skip = collector.timeline.len().saturating_sub(take);
+
+    collector.timeline.iter().skip(skip).copied().collect()
+}
+
+fn stats(collector: &Collector, settings: &Settings) -> Stats {
+    let mut aggregate = Aggregate::new();
 
-    let page = &messages[from..];
+    for endpoint in &collector.endpoints {
+        aggregate.merge(endpoint);
+    }
+
+    stats::compute(&aggregate, &settings.percentiles)
+}
+
+fn endpoints(collector: &Collector, settings: &Settings) -> Vec<(String, Stats)> {
+    settings
+        .endpoints
+        .iter()
+        .zip(&collector.endpoints)
+        .map(|(endpoint, aggregate)| {
+            (
+                endpoint.name.to_string(),
+                stats::compute(aggregate, &settings.percentiles),
+            )
+        })
+        .collect()
+}
 
-    stats::compute(page)
+fn protocols(collector: &Collector, settings: &Settings) -> Vec<(String, Stats)> {
+    collector
+        .protocols
+        .iter()
+        .map(|(version, aggregate)| {
+            (
+                version.to_string(),
+                stats::compute(aggregate, &settings.percentiles),
+            )
+        })
+        .collect()
 }
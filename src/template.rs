@@ -0,0 +1,245 @@
+use chrono::Utc;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Shared by every worker so {{seq}} never repeats within a run.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    raw: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Uuid,
+    RandInt(i64, i64),
+    Seq,
+    Timestamp,
+    WorkerId,
+    Variable(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Context {
+    pub worker: usize,
+    pub sequence: u64,
+    pub variables: HashMap<String, String>,
+}
+
+impl Context {
+    pub fn next(worker: usize) -> Self {
+        Self {
+            worker,
+            sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            variables: HashMap::new(),
+        }
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = raw;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find("}}")
+                .ok_or(format!("unclosed placeholder in {}", raw))?;
+
+            parts.push(Self::parse_placeholder(&rest[start + 2..start + end])?);
+
+            rest = &rest[start + end + 2..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Template {
+    // Text sent as it is, even where it looks like a placeholder.
+    pub fn literal(raw: &str) -> Self {
+        Self {
+            raw: raw.to_string(),
+            parts: vec![Part::Text(raw.to_string())],
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.parts.iter().all(|part| matches!(part, Part::Text(_)))
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut value = String::with_capacity(self.raw.len());
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => value.push_str(text),
+                Part::Uuid => value.push_str(&uuid()),
+                Part::RandInt(low, high) => {
+                    value.push_str(&thread_rng().gen_range(low, high + 1).to_string())
+                }
+                Part::Seq => value.push_str(&context.sequence.to_string()),
+                Part::Timestamp => value.push_str(&Utc::now().timestamp_millis().to_string()),
+                Part::WorkerId => value.push_str(&context.worker.to_string()),
+                Part::Variable(name) => {
+                    if let Some(variable) = context.variables.get(name) {
+                        value.push_str(variable);
+                    }
+                }
+            }
+        }
+
+        value
+    }
+
+    fn parse_placeholder(placeholder: &str) -> Result<Part, String> {
+        let words: Vec<&str> = placeholder.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["uuid"] => Ok(Part::Uuid),
+            ["seq"] => Ok(Part::Seq),
+            ["timestamp"] => Ok(Part::Timestamp),
+            ["workerId"] => Ok(Part::WorkerId),
+            ["randInt", low, high] => {
+                let low = Self::parse_integer(low)?;
+                let high = Self::parse_integer(high)?;
+
+                if low > high {
+                    return Err(format!("empty range in {{{{{}}}}}", placeholder));
+                }
+
+                Ok(Part::RandInt(low, high))
+            }
+            [name] if Self::is_variable(name) => Ok(Part::Variable(name.to_string())),
+            _ => Err(format!("unknown placeholder {{{{{}}}}}", placeholder)),
+        }
+    }
+
+    fn is_variable(name: &str) -> bool {
+        name.chars()
+            .all(|char| char.is_alphanumeric() || char == '_' || char == '-' || char == '.')
+    }
+
+    fn parse_integer(value: &str) -> Result<i64, String> {
+        value
+            .parse::<i64>()
+            .map_err(|_| format!("invalid integer {}", value))
+    }
+}
+
+// Random (version 4) UUID.
+fn uuid() -> String {
+    let mut bytes: [u8; 16] = thread_rng().gen();
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(raw: &str, context: &Context) -> String {
+        raw.parse::<Template>().unwrap().render(context)
+    }
+
+    #[test]
+    fn keeps_text_without_placeholders() {
+        let template: Template = "/items?page=1".parse().unwrap();
+
+        assert!(template.is_static());
+        assert_eq!(template.as_str(), "/items?page=1");
+        assert_eq!(template.render(&Context::default()), "/items?page=1");
+    }
+
+    #[test]
+    fn renders_builtin_placeholders() {
+        let context = Context {
+            worker: 3,
+            sequence: 42,
+            variables: HashMap::new(),
+        };
+
+        assert_eq!(render("/{{workerId}}/{{ seq }}", &context), "/3/42");
+
+        let number: i64 = render("{{randInt 5 7}}", &context).parse().unwrap();
+        assert!((5..=7).contains(&number));
+
+        let uuid = render("{{uuid}}", &context);
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+    }
+
+    #[test]
+    fn renders_variables() {
+        let template: Template = "{{user.id}}-{{missing}}".parse().unwrap();
+
+        let mut context = Context::default();
+        context
+            .variables
+            .insert("user.id".to_string(), "7".to_string());
+
+        assert!(!template.is_static());
+        assert_eq!(
+            template.variables().collect::<Vec<_>>(),
+            vec!["user.id", "missing"]
+        );
+        assert_eq!(template.render(&context), "7-");
+    }
+
+    #[test]
+    fn rejects_invalid_placeholders() {
+        assert!("{{uuid".parse::<Template>().is_err());
+        assert!("{{randInt 9 1}}".parse::<Template>().is_err());
+        assert!("{{randInt one 2}}".parse::<Template>().is_err());
+        assert!("{{random number}}".parse::<Template>().is_err());
+        assert!("{{a/b}}".parse::<Template>().is_err());
+    }
+}
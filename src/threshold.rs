@@ -0,0 +1,216 @@
+use crate::stats::format_time;
+use crate::summary::Summary;
+use serde::Serialize;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Metric {
+    Percentile(f64),
+    Minimum,
+    Mean,
+    Maximum,
+    Stddev,
+    Rate,
+    Requests,
+    ErrorRate,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operator {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub expression: String,
+    pub metric: Metric,
+    pub operator: Operator,
+    pub value: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThresholdResult {
+    pub expression: String,
+    pub actual: String,
+    pub passed: bool,
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression: String = expression.split_whitespace().collect();
+
+        let (index, operator, length) = ["<=", ">=", "<", ">"]
+            .iter()
+            .filter_map(|token| {
+                expression
+                    .find(token)
+                    .map(|index| (index, *token, token.len()))
+            })
+            .min_by_key(|(index, _, length)| (*index, usize::MAX - length))
+            .ok_or(format!("missing operator in threshold {}", expression))?;
+
+        let operator = match operator {
+            "<=" => Operator::LessEqual,
+            ">=" => Operator::GreaterEqual,
+            "<" => Operator::Less,
+            _ => Operator::Greater,
+        };
+
+        let metric = Self::parse_metric(&expression[..index])?;
+        let value = Self::parse_value(metric, &expression[index + length..])?;
+
+        Ok(Self {
+            expression,
+            metric,
+            operator,
+            value,
+        })
+    }
+}
+
+impl Threshold {
+    pub fn evaluate(&self, summary: &Summary) -> ThresholdResult {
+        let stats = &summary.stats;
+
+        let actual = match self.metric {
+            Metric::Percentile(percentile) => stats
+                .time_percentiles
+                .iter()
+                .find(|(value, _)| *value == percentile)
+                .map(|(_, value)| *value as f64)
+                .unwrap_or(0f64),
+            Metric::Minimum => stats.time_minimum as f64,
+            Metric::Mean => stats.time_mean as f64,
+            Metric::Maximum => stats.time_maximum as f64,
+            Metric::Stddev => stats.time_stddev as f64,
+            Metric::Rate => summary.rate,
+            Metric::Requests => stats.count as f64,
+            Metric::ErrorRate => stats.error_count as f64 / (stats.count as f64).max(1f64),
+        };
+
+        let passed = match self.operator {
+            Operator::Less => actual < self.value,
+            Operator::LessEqual => actual <= self.value,
+            Operator::Greater => actual > self.value,
+            Operator::GreaterEqual => actual >= self.value,
+        };
+
+        let actual = match self.metric {
+            Metric::Rate => format!("{:.2}req/s", actual),
+            Metric::Requests => format!("{}", actual),
+            Metric::ErrorRate => format!("{:.2}%", actual * 100f64),
+            _ => format_time(actual as u64),
+        };
+
+        ThresholdResult {
+            expression: self.expression.to_string(),
+            actual,
+            passed,
+        }
+    }
+
+    fn parse_metric(name: &str) -> Result<Metric, String> {
+        match name {
+            "min" => Ok(Metric::Minimum),
+            "mean" => Ok(Metric::Mean),
+            "max" => Ok(Metric::Maximum),
+            "stddev" => Ok(Metric::Stddev),
+            "rate" => Ok(Metric::Rate),
+            "requests" => Ok(Metric::Requests),
+            "error_rate" => Ok(Metric::ErrorRate),
+            _ if name.starts_with('p') => name[1..]
+                .parse::<f64>()
+                .ok()
+                .filter(|percentile| (0f64..=100f64).contains(percentile))
+                .map(Metric::Percentile)
+                .ok_or_else(|| format!("invalid percentile {}", name)),
+            _ => Err(format!("unknown threshold metric {}", name)),
+        }
+    }
+
+    // Times are compared in microseconds and error rates as a fraction.
+    fn parse_value(metric: Metric, value: &str) -> Result<f64, String> {
+        let (number, scale) = match metric {
+            Metric::Rate | Metric::Requests => (value, 1f64),
+            Metric::ErrorRate => match value.strip_suffix('%') {
+                Some(number) => (number, 0.01f64),
+                None => (value, 1f64),
+            },
+            _ => {
+                if let Some(number) = value.strip_suffix("us") {
+                    (number, 1f64)
+                } else if let Some(number) = value.strip_suffix("ms") {
+                    (number, 1_000f64)
+                } else if let Some(number) = value.strip_suffix('s') {
+                    (number, 1_000_000f64)
+                } else {
+                    (value, 1_000f64)
+                }
+            }
+        };
+
+        number
+            .parse::<f64>()
+            .map(|number| number * scale)
+            .map_err(|_| format!("invalid threshold value {}", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_percentile_in_milliseconds() {
+        let threshold: Threshold = "p95 < 200ms".parse().unwrap();
+
+        assert_eq!(threshold.expression, "p95<200ms");
+        assert_eq!(threshold.metric, Metric::Percentile(95f64));
+        assert_eq!(threshold.operator, Operator::Less);
+        assert_eq!(threshold.value, 200_000f64);
+    }
+
+    #[test]
+    fn parses_time_units() {
+        let value = |expression: &str| expression.parse::<Threshold>().unwrap().value;
+
+        assert_eq!(value("mean<500us"), 500f64);
+        assert_eq!(value("max<2s"), 2_000_000f64);
+        assert_eq!(value("p99.9<15"), 15_000f64);
+    }
+
+    #[test]
+    fn prefers_the_longest_operator() {
+        let threshold: Threshold = "rate>=500".parse().unwrap();
+
+        assert_eq!(threshold.metric, Metric::Rate);
+        assert_eq!(threshold.operator, Operator::GreaterEqual);
+        assert_eq!(threshold.value, 500f64);
+    }
+
+    #[test]
+    fn parses_error_rate_as_a_fraction() {
+        let percent: Threshold = "error_rate<1%".parse().unwrap();
+        let fraction: Threshold = "error_rate<=0.05".parse().unwrap();
+
+        assert_eq!(percent.metric, Metric::ErrorRate);
+        assert_eq!(percent.value, 0.01f64);
+        assert_eq!(fraction.operator, Operator::LessEqual);
+        assert_eq!(fraction.value, 0.05f64);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!("p95 200ms".parse::<Threshold>().is_err());
+        assert!("latency<200ms".parse::<Threshold>().is_err());
+        assert!("pxx<200ms".parse::<Threshold>().is_err());
+        assert!("p150<200ms".parse::<Threshold>().is_err());
+        assert!("pNaN<200ms".parse::<Threshold>().is_err());
+        assert!("rate>fast".parse::<Threshold>().is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use native_tls::{Certificate, Identity, Protocol, TlsConnector};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time;
+use url::Url;
+
+// Negotiated version found by the probe, once it finished.
+static VERSION: Mutex<Option<String>> = Mutex::new(None);
+
+// TLS record and handshake message types, and the extension a TLS 1.3 server names its
+// version in, since the version field of its hello stays at TLS 1.2.
+const HANDSHAKE_RECORD: u8 = 22;
+const SERVER_HELLO: u8 = 2;
+const SUPPORTED_VERSIONS: u16 = 43;
+
+#[derive(Clone, Default)]
+pub struct Tls {
+    pub insecure: bool,
+    pub roots: Vec<Certificate>,
+    pub identity: Option<Identity>,
+    pub min_version: Option<TlsVersion>,
+    pub max_version: Option<TlsVersion>,
+    // Sent through SNI and verified against the certificate in place of the URL host.
+    pub server_name: Option<String>,
+}
+
+// Keeps what the server sends during the handshake, which starts with its hello.
+struct Recorder {
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "1.0" => TlsVersion::Tls10,
+            "1.1" => TlsVersion::Tls11,
+            "1.3" => TlsVersion::Tls13,
+            _ => TlsVersion::Tls12,
+        }
+    }
+
+    fn protocol(self) -> Protocol {
+        match self {
+            TlsVersion::Tls10 => Protocol::Tlsv10,
+            TlsVersion::Tls11 => Protocol::Tlsv11,
+            TlsVersion::Tls12 => Protocol::Tlsv12,
+            TlsVersion::Tls13 => Protocol::Tlsv13,
+        }
+    }
+}
+
+impl Tls {
+    // HTTP/2 is only offered through ALPN when asked for, since the connection then has
+    // to speak it.
+    pub fn connector(&self, http2: bool) -> TlsConnector {
+        let mut builder = TlsConnector::builder();
+
+        if http2 {
+            builder.request_alpns(&["h2"]);
+        }
+
+        builder
+            .danger_accept_invalid_certs(self.insecure)
+            .danger_accept_invalid_hostnames(self.insecure)
+            .min_protocol_version(self.min_version.map(TlsVersion::protocol))
+            .max_protocol_version(self.max_version.map(TlsVersion::protocol));
+
+        for root in &self.roots {
+            builder.add_root_certificate(root.clone());
+        }
+
+        if let Some(identity) = &self.identity {
+            builder.identity(identity.clone());
+        }
+
+        builder.build().expect("tls")
+    }
+
+    // native-tls does not report the TLS version, so one handshake with the connector the
+    // requests use tells which one the server picks.
+    pub fn probe(&self, url: &Url, http2: bool, timeout: time::Duration) -> Option<String> {
+        let host = url.host_str()?;
+        let port = url.port_or_known_default()?;
+
+        let address = (host, port).to_socket_addrs().ok()?.next()?;
+        let stream = TcpStream::connect_timeout(&address, timeout).ok()?;
+
+        stream.set_read_timeout(Some(timeout)).ok()?;
+        stream.set_write_timeout(Some(timeout)).ok()?;
+
+        let recorder = Recorder {
+            stream,
+            received: Vec::new(),
+        };
+
+        let server_name = self.server_name.as_deref().unwrap_or(host);
+        let stream = self.connector(http2).connect(server_name, recorder).ok()?;
+
+        server_version(&stream.get_ref().received)
+    }
+}
+
+impl fmt::Debug for Tls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tls")
+            .field("insecure", &self.insecure)
+            .field("roots", &self.roots.len())
+            .field("identity", &self.identity.is_some())
+            .field("min_version", &self.min_version)
+            .field("max_version", &self.max_version)
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+impl Read for Recorder {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let count = self.stream.read(buffer)?;
+
+        self.received.extend_from_slice(&buffer[..count]);
+
+        Ok(count)
+    }
+}
+
+impl Write for Recorder {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.stream.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// Finds the negotiated TLS version of the first HTTPS endpoint in the background, so a slow
+// server does not hold up the start of the run.
+pub fn spawn_probe(tls: &Tls, url: Url, http2: bool, timeout: time::Duration) {
+    let tls = tls.clone();
+
+    tokio::task::spawn_blocking(move || {
+        if let Some(version) = tls.probe(&url, http2, timeout) {
+            *VERSION.lock().expect("tls version") = Some(version);
+        }
+    });
+}
+
+pub fn version() -> Option<String> {
+    VERSION.lock().expect("tls version").clone()
+}
+
+// The version the server picked in its hello, named the way OpenSSL names it.
+fn server_version(received: &[u8]) -> Option<String> {
+    let mut handshake = Vec::new();
+    let mut rest = received;
+
+    // The hello may be split across several handshake records.
+    while rest.len() >= 5 && rest[0] == HANDSHAKE_RECORD {
+        let length = u16::from_be_bytes([rest[3], rest[4]]) as usize;
+        let record = rest.get(5..5 + length)?;
+
+        handshake.extend_from_slice(record);
+        rest = &rest[5 + length..];
+    }
+
+    let mut reader = Reader(&handshake);
+
+    if reader.take(1)?[0] != SERVER_HELLO {
+        return None;
+    }
+
+    reader.take(3)?;
+
+    let mut version = reader.number()?;
+
+    // Random, session id, cipher suite and compression method.
+    reader.take(32)?;
+    let session = reader.take(1)?[0] as usize;
+    reader.take(session + 3)?;
+
+    // Servers before TLS 1.2 may send no extensions at all.
+    if let Some(length) = reader.number() {
+        let mut extensions = Reader(reader.take(length as usize)?);
+
+        while let (Some(kind), Some(length)) = (extensions.number(), extensions.number()) {
+            let data = extensions.take(length as usize)?;
+
+            if kind == SUPPORTED_VERSIONS && data.len() == 2 {
+                version = u16::from_be_bytes([data[0], data[1]]);
+            }
+        }
+    }
+
+    let name = match version {
+        0x0301 => "TLSv1",
+        0x0302 => "TLSv1.1",
+        0x0303 => "TLSv1.2",
+        0x0304 => "TLSv1.3",
+        _ => return None,
+    };
+
+    Some(name.to_string())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.0.len() < count {
+            return None;
+        }
+
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+
+        Some(taken)
+    }
+
+    fn number(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+pub fn load_certificates(path: &str) -> Result<Vec<Certificate>, String> {
+    let pem = read(path)?;
+    let certificates = Certificate::stack_from_pem(&pem).map_err(|_| invalid(path))?;
+
+    if certificates.is_empty() {
+        return Err(invalid(path));
+    }
+
+    Ok(certificates)
+}
+
+// A PEM certificate with its PKCS#8 key in the same file or in a separate one, or a PKCS#12
+// archive. Only the archive may be encrypted, since keys are loaded the same way on every
+// platform.
+pub fn load_identity(
+    path: &str,
+    key_path: Option<&str>,
+    password: Option<&str>,
+) -> Result<Identity, String> {
+    let data = read(path)?;
+
+    let pem = String::from_utf8_lossy(&data).contains("-----BEGIN");
+
+    if !pem && key_path.is_none() {
+        return Identity::from_pkcs12(&data, password.unwrap_or_default())
+            .map_err(|_| format!("invalid PKCS#12 certificate {} or password", path));
+    }
+
+    load_certificates(path)?;
+
+    let (key_path, key) = match key_path {
+        Some(key_path) => (key_path, read(key_path)?),
+        None => (path, data.clone()),
+    };
+
+    let key = pem_block(&key, "PRIVATE KEY").ok_or_else(|| {
+        if pem_block(&key, "ENCRYPTED PRIVATE KEY").is_some() || password.is_some() {
+            format!(
+                "encrypted key {} is not supported, use a PKCS#12 archive",
+                key_path
+            )
+        } else {
+            format!("key {} is not an unencrypted PKCS#8 key", key_path)
+        }
+    })?;
+
+    Identity::from_pkcs8(&data, key.as_bytes()).map_err(|_| format!("invalid key {}", key_path))
+}
+
+// The first PEM block with exactly this label, such as PRIVATE KEY but not RSA PRIVATE KEY.
+fn pem_block(data: &[u8], label: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let start = text.find(&begin)?;
+    let stop = text[start..].find(&end)? + start + end.len();
+
+    Some(text[start..stop].to_string())
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|error| format!("cannot read {}: {}", path, error))
+}
+
+fn invalid(path: &str) -> String {
+    format!("invalid certificate {}", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A handshake record holding a server hello with the given version and extensions.
+    fn server_hello(version: u16, extensions: &[u8]) -> Vec<u8> {
+        let mut hello = version.to_be_bytes().to_vec();
+        hello.extend_from_slice(&[0; 32]);
+        hello.extend_from_slice(&[0, 0x13, 0x01, 0]);
+
+        if !extensions.is_empty() {
+            hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+            hello.extend_from_slice(extensions);
+        }
+
+        let mut message = vec![SERVER_HELLO, 0];
+        message.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+        message.extend_from_slice(&hello);
+
+        let mut record = vec![HANDSHAKE_RECORD, 3, 3];
+        record.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        record.extend_from_slice(&message);
+        record
+    }
+
+    #[test]
+    fn reads_the_version_of_the_hello() {
+        assert_eq!(
+            server_version(&server_hello(0x0303, &[])),
+            Some("TLSv1.2".to_string())
+        );
+        assert_eq!(
+            server_version(&server_hello(0x0301, &[0xff, 0x01, 0, 1, 0])),
+            Some("TLSv1".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_the_supported_versions_extension() {
+        let extensions = [0xff, 0x01, 0, 1, 0, 0, 43, 0, 2, 3, 4];
+
+        assert_eq!(
+            server_version(&server_hello(0x0303, &extensions)),
+            Some("TLSv1.3".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_a_hello_split_across_records() {
+        let record = server_hello(0x0303, &[]);
+        let (first, second) = record[5..].split_at(10);
+
+        let mut received = vec![HANDSHAKE_RECORD, 3, 3, 0, first.len() as u8];
+        received.extend_from_slice(first);
+        received.extend_from_slice(&[HANDSHAKE_RECORD, 3, 3, 0, second.len() as u8]);
+        received.extend_from_slice(second);
+
+        assert_eq!(server_version(&received), Some("TLSv1.2".to_string()));
+    }
+
+    #[test]
+    fn ignores_anything_else() {
+        assert_eq!(server_version(&[]), None);
+        assert_eq!(server_version(&[21, 3, 3, 0, 2, 2, 40]), None);
+        assert_eq!(server_version(&server_hello(0x0300, &[])[..20]), None);
+    }
+}
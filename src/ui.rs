@@ -1,8 +1,9 @@
+use crate::export;
 use crate::settings::Settings;
-use crate::summary;
+use crate::summary::{self, Collector, Summary};
 use crate::view;
 use crate::worker::{WorkerCommand, WorkerMessage};
-use std::io::{self, stdout, Bytes, Read};
+use std::io::{self, stdout, BufReader, Bytes, Read};
 use std::time;
 use termion::async_stdin;
 use termion::raw::IntoRawMode;
@@ -43,31 +44,24 @@ pub async fn render(
     settings: &Settings,
     mut receiver: mpsc::UnboundedReceiver<WorkerMessage>,
     sender: watch::Sender<WorkerCommand>,
-) -> () {
+) -> Summary {
     let mut terminal = create_terminal().expect("terminal");
     let mut terminal_stdin = create_terminal_stdin();
 
-    let mut messages = Vec::new();
-    let mut summary = summary::compute(&messages, settings, messages.len());
+    let mut collector = Collector::new(settings);
+    let mut summary = summary::compute(&collector, settings, 0);
 
-    let mut finished_workers = 0;
     let mut current_status = ViewStatus::Running;
     let mut previous_status = ViewStatus::Running;
 
     'render: loop {
-        while let Ok(message) = receiver.try_recv() {
-            if message.finished {
-                finished_workers += 1;
-            }
-
-            messages.push(message);
-        }
+        collector.drain(&mut receiver);
 
-        if finished_workers == settings.concurrency {
+        if collector.finished() {
             current_status = ViewStatus::Finished;
         }
 
-        'input: while let Some(input) = terminal_stdin.next() {
+        'input: for input in terminal_stdin.by_ref() {
             match InputCommand::from_input(input) {
                 InputCommand::Stop => {
                     current_status = ViewStatus::Finished;
@@ -83,17 +77,15 @@ pub async fn render(
         if current_status == ViewStatus::Finished && previous_status != ViewStatus::Finished {
             receiver.close();
 
-            match sender.broadcast(WorkerCommand::Stop) {
-                _ => (),
-            };
+            let _ = sender.broadcast(WorkerCommand::Stop);
 
-            summary = summary::compute(&messages, settings, messages.len());
+            summary = summary::compute(&collector, settings, 0);
         } else if current_status == ViewStatus::Running {
             let border = 2;
             let size = terminal.get_frame().size();
             let columns = (size.width - border) as usize;
 
-            summary = summary::compute(&messages, settings, columns);
+            summary = summary::compute(&collector, settings, columns);
         }
 
         terminal
@@ -109,6 +101,17 @@ pub async fn render(
 
         tokio::time::delay_for(time::Duration::from_millis(250)).await;
     }
+
+    let summary = summary::compute(&collector, settings, 0);
+
+    // Leave the alternate screen first, in case the summary is written to stdout.
+    drop(terminal);
+
+    if let Some(output) = &settings.output {
+        export::write_summary(&summary, output);
+    }
+
+    summary
 }
 
 fn create_terminal() -> io::Result<Terminal<impl Backend>> {
@@ -124,6 +127,6 @@ fn create_terminal() -> io::Result<Terminal<impl Backend>> {
     Ok(terminal)
 }
 
-fn create_terminal_stdin() -> Bytes<AsyncReader> {
-    async_stdin().bytes()
+fn create_terminal_stdin() -> Bytes<BufReader<AsyncReader>> {
+    BufReader::new(async_stdin()).bytes()
 }
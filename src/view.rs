@@ -1,3 +1,4 @@
+use crate::stats::{format_bytes, format_phase, format_protocol, format_size, format_time};
 use crate::summary::Summary;
 use tui::backend::Backend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
@@ -5,7 +6,7 @@ use tui::style::{Color, Modifier, Style};
 use tui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline, Text, Widget};
 use tui::Frame;
 
-pub fn draw_running(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
+pub fn draw_running(summary: &Summary, mut frame: Frame<impl Backend>) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -24,6 +25,7 @@ pub fn draw_running(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
             [
                 Constraint::Length(3),
                 Constraint::Min(6),
+                Constraint::Length(3),
                 Constraint::Length(5),
             ]
             .as_ref(),
@@ -35,13 +37,14 @@ pub fn draw_running(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
     let version = "http-storm/0.1.0";
 
     draw_layout_header(title, &mut frame, layout[0]);
-    draw_widget_progress(&summary, &mut frame, widgets[0]);
-    draw_widget_timeline(&summary, &mut frame, widgets[1]);
-    draw_widget_request(&summary, &mut frame, widgets[2]);
+    draw_widget_progress(summary, &mut frame, widgets[0]);
+    draw_widget_timeline(summary, &mut frame, widgets[1]);
+    draw_widget_percentiles(summary, &mut frame, widgets[2]);
+    draw_widget_request(summary, &mut frame, widgets[3]);
     draw_layout_footer(&commands, version, &mut frame, layout[2]);
 }
 
-pub fn draw_finished(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
+pub fn draw_finished(summary: &Summary, mut frame: Frame<impl Backend>) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -54,9 +57,20 @@ pub fn draw_finished(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
         )
         .split(frame.size());
 
+    let thresholds = summary.thresholds.len() as u16;
+    let errors = summary.stats.errors.len() as u16;
+
     let widgets = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(5), Constraint::Min(20)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(5),
+                Constraint::Min(20),
+                Constraint::Length(if errors > 0 { errors + 3 } else { 0 }),
+                Constraint::Length(if thresholds > 0 { thresholds + 2 } else { 0 }),
+            ]
+            .as_ref(),
+        )
         .split(layout[1]);
 
     let title = "HTTP Storm";
@@ -64,12 +78,32 @@ pub fn draw_finished(summary: &Summary, mut frame: Frame<impl Backend>) -> () {
     let version = "http-storm/0.1.0";
 
     draw_layout_header(title, &mut frame, layout[0]);
-    draw_widget_request(&summary, &mut frame, widgets[0]);
-    draw_widget_stats(&summary, &mut frame, widgets[1]);
+    draw_widget_request(summary, &mut frame, widgets[0]);
+
+    if summary.endpoints.len() > 1 {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(widgets[1]);
+
+        draw_widget_stats(summary, &mut frame, columns[0]);
+        draw_widget_endpoints(summary, &mut frame, columns[1]);
+    } else {
+        draw_widget_stats(summary, &mut frame, widgets[1]);
+    }
+
+    if errors > 0 {
+        draw_widget_errors(summary, &mut frame, widgets[2]);
+    }
+
+    if thresholds > 0 {
+        draw_widget_thresholds(summary, &mut frame, widgets[3]);
+    }
+
     draw_layout_footer(&commands, version, &mut frame, layout[2]);
 }
 
-fn draw_layout_header(title: &str, frame: &mut Frame<impl Backend>, chunk: Rect) -> () {
+fn draw_layout_header(title: &str, frame: &mut Frame<impl Backend>, chunk: Rect) {
     Paragraph::new([Text::raw(title)].iter())
         .block(block_default())
         .style(style_bold(Color::Blue))
@@ -82,7 +116,7 @@ fn draw_layout_footer(
     version: &str,
     frame: &mut Frame<impl Backend>,
     chunk: Rect,
-) -> () {
+) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
@@ -119,31 +153,73 @@ fn draw_layout_footer(
         .render(frame, chunks[1]);
 }
 
-fn draw_widget_progress(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) -> () {
+fn draw_widget_progress(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let label = match &summary.stage {
+        Some(stage) => format!("{}% - {}", summary.progress_percent, stage),
+        None => format!("{}%", summary.progress_percent),
+    };
+
     Gauge::default()
         .block(block_default())
         .style(style_default(Color::Gray).bg(Color::DarkGray))
         .percent(summary.progress_percent)
+        .label(&label)
         .render(frame, chunk);
 }
 
-fn draw_widget_timeline(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) -> () {
+fn draw_widget_timeline(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let title = summary.stage.as_deref().unwrap_or_default();
+
     Sparkline::default()
-        .block(block_default())
+        .block(block_default().title(title))
         .style(style_default(Color::LightGreen))
-        .data(&summary.stats.time_values)
+        .data(&summary.timeline)
         .max(summary.stats.time_mean * 2)
         .render(frame, chunk);
 }
 
-fn draw_widget_request(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) -> () {
+fn draw_widget_percentiles(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let mut text = Vec::new();
+
+    for (percentile, value) in &summary.stats.time_percentiles {
+        text.push(Text::styled(
+            format!("p{}: ", percentile),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!("{}   ", format_time(*value)),
+            style_bold(Color::LightGreen),
+        ));
+    }
+
+    Paragraph::new(text.iter())
+        .block(block_default())
+        .style(style_default(Color::Gray))
+        .alignment(Alignment::Left)
+        .render(frame, chunk);
+}
+
+fn draw_widget_request(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
     let method = Text::styled(&summary.request_method, style_bold(Color::Green));
     let url = Text::styled(&summary.request_url, style_bold(Color::Blue));
 
-    let count = Text::styled(
-        format!("{} requests", &summary.total_count),
-        style_bold(Color::Gray),
-    );
+    let tls = match &summary.tls_version {
+        Some(tls_version) => format!(" ({})", tls_version),
+        None => String::new(),
+    };
+
+    let tls = Text::styled(tls, style_bold(Color::Gray));
+
+    let count = match summary.delayed_count {
+        Some(delayed_count) => format!(
+            "{} requests ({} delayed by concurrency)",
+            summary.total_count, delayed_count
+        ),
+        None => format!("{} requests", summary.total_count),
+    };
+
+    let count = Text::styled(count, style_bold(Color::Gray));
 
     let elapsed = Text::styled(
         format!("{}s", &summary.elapsed_seconds),
@@ -154,6 +230,7 @@ fn draw_widget_request(summary: &Summary, frame: &mut Frame<impl Backend>, chunk
         method,
         Text::raw(" "),
         url,
+        tls,
         Text::raw("\n"),
         count,
         Text::raw("\n"),
@@ -167,60 +244,257 @@ fn draw_widget_request(summary: &Summary, frame: &mut Frame<impl Backend>, chunk
         .render(frame, chunk);
 }
 
-fn draw_widget_stats(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) -> () {
+fn draw_widget_stats(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
     let mut text = Vec::new();
 
-    let rate = summary.total_count as f64 / summary.elapsed_seconds as f64;
+    text.push(Text::styled(
+        format!("Rate: {:.2}req/s\n", summary.rate),
+        style_bold(Color::Gray),
+    ));
+
+    text.push(Text::styled(
+        format!(
+            "Received: {} ({}/s)\n",
+            format_bytes(summary.stats.response_bytes as f64),
+            format_bytes(summary.throughput)
+        ),
+        style_bold(Color::Gray),
+    ));
+
+    text.push(Text::styled(
+        format!(
+            "Sent: {}\n",
+            format_bytes(summary.stats.request_bytes as f64)
+        ),
+        style_bold(Color::Gray),
+    ));
 
     text.push(Text::styled(
-        format!("Rate: {:.2}req/s\n", rate),
+        format!(
+            "Connections: {} new / {} reused\n",
+            summary.connections_opened, summary.connections_reused
+        ),
         style_bold(Color::Gray),
     ));
 
     text.push(Text::styled(
-        format!("Fastest: {}ms\n", summary.stats.time_minimum),
+        format!("Fastest: {}\n", format_time(summary.stats.time_minimum)),
         style_bold(Color::Gray),
     ));
 
     text.push(Text::styled(
-        format!("Slowest: {}ms\n", summary.stats.time_maximum),
+        format!("Slowest: {}\n", format_time(summary.stats.time_maximum)),
         style_bold(Color::Gray),
     ));
 
     text.push(Text::styled(
-        format!("Mean: {}ms\n", summary.stats.time_mean),
+        format!("Mean: {}\n", format_time(summary.stats.time_mean)),
         style_bold(Color::Gray),
     ));
 
     text.push(Text::styled(
-        format!("Standard Deviation: {}ms\n", summary.stats.time_stddev),
+        format!(
+            "Standard Deviation: {}\n",
+            format_time(summary.stats.time_stddev)
+        ),
         style_bold(Color::Gray),
     ));
 
-    text.push(Text::styled(format!("\n"), style_bold(Color::Gray)));
+    for (percentile, value) in &summary.stats.time_percentiles {
+        text.push(Text::styled(
+            format!("p{}: {}\n", percentile, format_time(*value)),
+            style_bold(Color::Gray),
+        ));
+    }
+
+    text.push(Text::styled("\n", style_bold(Color::Gray)));
+
+    for phase in summary.stats.phases.iter().filter(|phase| phase.count > 0) {
+        text.push(Text::styled(
+            format!("{}\n", format_phase(phase)),
+            style_bold(Color::Gray),
+        ));
+    }
+
+    text.push(Text::styled(
+        format!("{}\n", format_size(&summary.stats)),
+        style_bold(Color::Gray),
+    ));
+
+    if summary.stats.corrected_count > 0 {
+        text.push(Text::styled("\n", style_bold(Color::Gray)));
+
+        text.push(Text::styled(
+            "Response Time (from intended start)\n",
+            style_bold(Color::Blue),
+        ));
+
+        text.push(Text::styled(
+            format!(
+                "Fastest: {}\n",
+                format_time(summary.stats.corrected_minimum)
+            ),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!(
+                "Slowest: {}\n",
+                format_time(summary.stats.corrected_maximum)
+            ),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!("Mean: {}\n", format_time(summary.stats.corrected_mean)),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!(
+                "Standard Deviation: {}\n",
+                format_time(summary.stats.corrected_stddev)
+            ),
+            style_bold(Color::Gray),
+        ));
+
+        for (percentile, value) in &summary.stats.corrected_percentiles {
+            text.push(Text::styled(
+                format!("p{}: {}\n", percentile, format_time(*value)),
+                style_bold(Color::Gray),
+            ));
+        }
+    }
+
+    text.push(Text::styled("\n".to_string(), style_bold(Color::Gray)));
 
     for ((lower, upper), count) in &summary.stats.time_histogram {
         text.push(Text::styled(
-            format!("{}ms ~ {}ms: {}\n", lower, upper, count),
+            format!(
+                "{} ~ {}: {}\n",
+                format_time(*lower),
+                format_time(*upper),
+                count
+            ),
             style_bold(Color::Gray),
         ));
     }
 
-    text.push(Text::styled(format!("\n"), style_bold(Color::Gray)));
+    text.push(Text::styled("\n".to_string(), style_bold(Color::Gray)));
 
     for (status, count) in &summary.stats.status {
-        let color = match &status[0..1] {
-            "2" => Color::Green,
-            "3" => Color::Yellow,
-            "4" => Color::Red,
-            "5" => Color::Red,
-            "F" => Color::Red,
-            _ => Color::Gray,
+        text.push(Text::styled(
+            format!("Status {}: {}\n", status, count),
+            style_bold(status_color(status)),
+        ));
+    }
+
+    if !summary.protocols.is_empty() {
+        text.push(Text::styled("\n", style_bold(Color::Gray)));
+
+        for (version, stats) in &summary.protocols {
+            text.push(Text::styled(
+                format!("Protocol {}\n", format_protocol(version, stats)),
+                style_bold(Color::Gray),
+            ));
+        }
+    }
+
+    Paragraph::new(text.iter())
+        .block(block_default())
+        .style(style_default(Color::Gray))
+        .alignment(Alignment::Left)
+        .render(frame, chunk);
+}
+
+fn draw_widget_endpoints(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let mut text = Vec::new();
+
+    for (name, stats) in &summary.endpoints {
+        text.push(Text::styled(format!("{}\n", name), style_bold(Color::Blue)));
+
+        if stats.count == 0 {
+            text.push(Text::styled("Requests: 0\n\n", style_bold(Color::Gray)));
+            continue;
+        }
+
+        text.push(Text::styled(
+            format!("Requests: {}\n", stats.count),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!(
+                "Fastest: {} / Mean: {} / Slowest: {}\n",
+                format_time(stats.time_minimum),
+                format_time(stats.time_mean),
+                format_time(stats.time_maximum)
+            ),
+            style_bold(Color::Gray),
+        ));
+
+        for (status, count) in &stats.status {
+            text.push(Text::styled(
+                format!("Status {}: {}\n", status, count),
+                style_bold(status_color(status)),
+            ));
+        }
+
+        text.push(Text::styled("\n", style_bold(Color::Gray)));
+    }
+
+    Paragraph::new(text.iter())
+        .block(block_default())
+        .style(style_default(Color::Gray))
+        .alignment(Alignment::Left)
+        .render(frame, chunk);
+}
+
+fn draw_widget_errors(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let mut text = vec![Text::styled(
+        format!("{:>8}  {:<20}{}\n", "Count", "Error", "Message"),
+        style_bold(Color::Blue),
+    )];
+
+    for error in &summary.stats.errors {
+        text.push(Text::styled(
+            format!("{:>8}  ", error.count),
+            style_bold(Color::Gray),
+        ));
+
+        text.push(Text::styled(
+            format!("{:<20}", error.kind),
+            style_bold(Color::Red),
+        ));
+
+        text.push(Text::styled(
+            format!("{}\n", error.message),
+            style_bold(Color::Gray),
+        ));
+    }
+
+    Paragraph::new(text.iter())
+        .block(block_default())
+        .style(style_default(Color::Gray))
+        .alignment(Alignment::Left)
+        .render(frame, chunk);
+}
+
+fn draw_widget_thresholds(summary: &Summary, frame: &mut Frame<impl Backend>, chunk: Rect) {
+    let mut text = Vec::new();
+
+    for threshold in &summary.thresholds {
+        let (result, color) = if threshold.passed {
+            ("PASS", Color::Green)
+        } else {
+            ("FAIL", Color::Red)
         };
 
+        text.push(Text::styled(format!("{} ", result), style_bold(color)));
+
         text.push(Text::styled(
-            format!("Status {}: {}\n", status, count),
-            style_bold(color),
+            format!("{} ({})\n", threshold.expression, threshold.actual),
+            style_bold(Color::Gray),
         ));
     }
 
@@ -231,6 +505,16 @@ fn draw_widget_stats(summary: &Summary, frame: &mut Frame<impl Backend>, chunk:
         .render(frame, chunk);
 }
 
+// Anything other than a status code is an error kind.
+fn status_color(status: &str) -> Color {
+    match &status[0..1] {
+        "1" => Color::Gray,
+        "2" => Color::Green,
+        "3" => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
 fn block_default<'a>() -> Block<'a> {
     Block::default()
         .border_style(style_default(Color::Black))
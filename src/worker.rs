@@ -1,11 +1,22 @@
+use crate::client::Client;
 use crate::metric::RequestMetric;
-use crate::settings::Settings;
+use crate::profile::Profile;
+use crate::replay::Replay;
+use crate::settings::{HttpVersion, Settings};
+use crate::template::Context;
+use crate::tls;
 use chrono::{DateTime, Duration, Utc};
-use reqwest::Client;
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time;
-use tokio;
-use tokio::sync::{mpsc, watch};
+use tokio::sync::{mpsc, watch, Barrier};
+use tokio::time::Instant;
+use url::Url;
+
+// How long an idle worker waits before checking the profile again.
+const PROFILE_DELAY: time::Duration = time::Duration::from_millis(50);
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WorkerCommand {
@@ -17,12 +28,90 @@ pub enum WorkerCommand {
 pub struct WorkerMessage {
     pub id: usize,
     pub finished: bool,
+    #[allow(dead_code)]
     pub start_time: DateTime<Utc>,
+    #[allow(dead_code)]
     pub current_time: DateTime<Utc>,
     pub elapsed_time: Duration,
+    pub delayed: bool,
     pub metric: RequestMetric,
 }
 
+#[derive(Debug)]
+struct Schedule {
+    ready: Barrier,
+    start_time: Mutex<Option<(Instant, DateTime<Utc>)>>,
+    rate: f64,
+    profile: Option<Profile>,
+    replay: Option<Replay>,
+    total: Option<u64>,
+    next: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Slot {
+    index: u64,
+    time: Instant,
+    intended_time: DateTime<Utc>,
+}
+
+impl Schedule {
+    fn new(
+        workers: usize,
+        rate: u64,
+        profile: Option<Profile>,
+        replay: Option<Replay>,
+        total: Option<u64>,
+    ) -> Self {
+        Self {
+            ready: Barrier::new(workers),
+            start_time: Mutex::new(None),
+            rate: rate as f64,
+            profile,
+            replay,
+            total,
+            next: AtomicU64::new(0),
+        }
+    }
+
+    async fn start(&self) {
+        self.ready.wait().await;
+    }
+
+    fn claim(&self) -> Option<Slot> {
+        let index = self.next.fetch_add(1, Ordering::SeqCst);
+
+        if let Some(total) = self.total {
+            if index >= total {
+                return None;
+            }
+        }
+
+        let (start_time, start_utc) = *self
+            .start_time
+            .lock()
+            .expect("schedule")
+            .get_or_insert_with(|| (Instant::now(), Utc::now()));
+
+        let offset = match (&self.replay, &self.profile) {
+            (Some(replay), _) => replay.offset(index as usize)?,
+            (None, Some(profile)) => profile.offset(index)?,
+            (None, None) => index as f64 / self.rate,
+        };
+
+        let offset = time::Duration::from_secs_f64(offset);
+
+        let time = start_time + offset;
+        let intended_time = start_utc + Duration::from_std(offset).expect("offset");
+
+        Some(Slot {
+            index,
+            time,
+            intended_time,
+        })
+    }
+}
+
 impl fmt::Display for WorkerMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Worker {:>3}: {}", self.id, self.metric)
@@ -33,22 +122,88 @@ pub fn collect_metrics(
     settings: &Settings,
     sender: mpsc::UnboundedSender<WorkerMessage>,
     receiver: watch::Receiver<WorkerCommand>,
-) -> () {
+) {
+    let schedule = if settings.open_model {
+        Some(Arc::new(Schedule::new(
+            settings.concurrency,
+            settings.rate.unwrap_or_default(),
+            settings.profile.clone(),
+            // Only a paced replay takes its schedule from the log, otherwise the rate sets it.
+            settings.replay.clone().filter(|replay| replay.paced()),
+            settings.total,
+        )))
+    } else {
+        None
+    };
+
+    probe_tls(settings);
+
+    // Without a shared client every worker builds its own, with its own connections.
+    let client = if settings.shared_client {
+        Some(Client::new(settings))
+    } else {
+        None
+    };
+
     for id in 1..settings.concurrency {
-        spawn_worker(settings.clone(), id, sender.clone(), receiver.clone());
+        spawn_worker(
+            settings.clone(),
+            id,
+            client.clone(),
+            schedule.clone(),
+            sender.clone(),
+            receiver.clone(),
+        );
     }
 
-    spawn_worker(settings.clone(), settings.concurrency, sender, receiver);
+    spawn_worker(
+        settings.clone(),
+        settings.concurrency,
+        client,
+        schedule,
+        sender,
+        receiver,
+    );
+}
+
+// The negotiated TLS version of the first HTTPS endpoint, shown next to the request.
+fn probe_tls(settings: &Settings) {
+    let url = settings
+        .endpoints
+        .iter()
+        .filter_map(|endpoint| Url::parse(&endpoint.url.render(&Context::default())).ok())
+        .find(|url| url.scheme() == "https");
+
+    let timeout = settings
+        .connect_timeout
+        .or(settings.timeout)
+        .unwrap_or(time::Duration::from_secs(5));
+
+    if let Some(url) = url {
+        tls::spawn_probe(
+            &settings.tls,
+            url,
+            settings.http_version == HttpVersion::Http2,
+            timeout,
+        );
+    }
 }
 
 fn spawn_worker(
     settings: Settings,
     id: usize,
+    client: Option<Client>,
+    schedule: Option<Arc<Schedule>>,
     sender: mpsc::UnboundedSender<WorkerMessage>,
     receiver: watch::Receiver<WorkerCommand>,
-) -> () {
+) {
     tokio::spawn(async move {
-        let client = Client::new();
+        let client = client.unwrap_or_else(|| Client::new(&settings));
+
+        // The schedule starts once every worker has its client ready.
+        if let Some(schedule) = &schedule {
+            schedule.start().await;
+        }
 
         let workers = settings.concurrency as f64;
         let worker_rate = settings.rate.map(|rate| rate as f64 / workers);
@@ -56,15 +211,150 @@ fn spawn_worker(
         let worker_duration = settings.duration;
 
         let start_time = Utc::now();
+        let mut previous_time = None;
+        let mut count = 0u64;
+
+        // Values taken from the data file and extracted from responses, kept per worker.
+        let mut variables = HashMap::new();
+        let mut step = 0;
 
-        for count in 1u64.. {
+        loop {
             let command = { *receiver.borrow() };
 
             if command == WorkerCommand::Stop {
                 break;
             }
 
-            let metric = RequestMetric::collect_metric(&client, &settings).await;
+            // A profile holds workers back until the stage needs them, so an idle worker
+            // finishes without sending anything once the profile is over.
+            if let Some(profile) = &settings.profile {
+                let elapsed_time = Utc::now().signed_duration_since(start_time);
+
+                if !duration_check(worker_duration, elapsed_time) {
+                    break;
+                }
+
+                if !profile_active(profile, &settings, id, elapsed_time) {
+                    previous_time = None;
+                    tokio::time::delay_for(PROFILE_DELAY).await;
+                    continue;
+                }
+
+                if schedule.is_none() {
+                    let rate = profile_rate(profile, &settings, elapsed_time);
+
+                    if let Some(delay_time) = profile_delay(rate, previous_time) {
+                        tokio::time::delay_for(delay_time.min(PROFILE_DELAY)).await;
+                        continue;
+                    }
+                }
+            }
+
+            // A worker takes its slot only once it is active, so one held back by the profile
+            // leaves its turn to the workers that are.
+            let slot = match &schedule {
+                Some(schedule) => match schedule.claim() {
+                    Some(slot) => Some(slot),
+                    None => break,
+                },
+                None => None,
+            };
+
+            // No worker was free when the slot was due, so the concurrency cap held it back.
+            let delayed = match &slot {
+                Some(slot) => {
+                    let late = Instant::now() > slot.time + time::Duration::from_millis(1);
+
+                    tokio::time::delay_until(slot.time).await;
+
+                    late
+                }
+                None => false,
+            };
+
+            let intended_time = match (&slot, &settings.profile) {
+                (Some(slot), _) => Some(slot.intended_time),
+                (None, Some(profile)) => {
+                    let elapsed_time = Utc::now().signed_duration_since(start_time);
+
+                    intended_time(
+                        profile_rate(profile, &settings, elapsed_time),
+                        previous_time,
+                    )
+                }
+                (None, None) => intended_time(worker_rate, previous_time),
+            };
+
+            // A session takes the next row of the data file once per iteration of its steps.
+            if let Some(feeder) = &settings.feeder {
+                if !settings.session || step == 0 {
+                    match feeder.next(id) {
+                        Some(row) => variables.extend(row.clone()),
+                        None => break,
+                    }
+                }
+            }
+
+            // A replayed log line picks the endpoint of its method and fills in its path.
+            let endpoint = if let Some(replay) = &settings.replay {
+                let index = match &slot {
+                    Some(slot) => slot.index as usize,
+                    None => replay.next(),
+                };
+
+                match replay.get(index) {
+                    Some((endpoint, row)) => {
+                        variables.extend(row);
+                        endpoint
+                    }
+                    None => break,
+                }
+            } else if settings.session {
+                step
+            } else {
+                settings.pick_endpoint()
+            };
+
+            // Recorded timing waits before the request, unless the schedule sets the pace.
+            let intended_time = match (settings.endpoints[endpoint].delay, &schedule) {
+                (Some(delay), None) => {
+                    tokio::time::delay_for(delay).await;
+
+                    if *receiver.borrow() == WorkerCommand::Stop {
+                        break;
+                    }
+
+                    intended_time.map(|time| time + Duration::from_std(delay).expect("delay"))
+                }
+                _ => intended_time,
+            };
+
+            // Placeholders are evaluated once per request.
+            let mut context = Context::next(id);
+            context.variables = variables.clone();
+
+            let mut metric = RequestMetric::collect_metric(
+                &client,
+                &settings,
+                endpoint,
+                &context,
+                intended_time,
+            )
+            .await;
+            count += 1;
+
+            variables.extend(metric.extracted.drain());
+
+            if settings.session {
+                step = if metric.is_error() && settings.endpoints[endpoint].restart {
+                    0
+                } else {
+                    (step + 1) % settings.endpoints.len()
+                };
+            }
+
+            // Without a global schedule the next request is due one interval after this one.
+            previous_time = Some(metric.start_time);
             let metric_elapsed_time = metric.elapsed_time;
 
             let current_time = Utc::now();
@@ -72,7 +362,8 @@ fn spawn_worker(
 
             let mut finished = false;
 
-            if !total_check(worker_total, count) {
+            // With a schedule the worker finds out it is done when no slot is left to claim.
+            if schedule.is_none() && !total_check(worker_total, count) {
                 finished = true;
             }
 
@@ -81,12 +372,13 @@ fn spawn_worker(
             }
 
             let message = WorkerMessage {
-                id: id,
-                start_time: start_time,
-                current_time: current_time,
-                elapsed_time: elapsed_time,
-                metric: metric,
-                finished: finished,
+                id,
+                start_time,
+                current_time,
+                elapsed_time,
+                delayed,
+                metric,
+                finished,
             };
 
             match sender.send(message) {
@@ -98,11 +390,64 @@ fn spawn_worker(
                 break;
             }
 
-            rate_delay(worker_rate, metric_elapsed_time).await;
+            if schedule.is_none() && settings.profile.is_none() {
+                rate_delay(worker_rate, metric_elapsed_time).await;
+            }
         }
     });
 }
 
+// Share of the current stage rate for each active worker.
+fn profile_rate(profile: &Profile, settings: &Settings, elapsed_time: Duration) -> Option<f64> {
+    let elapsed = elapsed_time.num_milliseconds() as f64 / 1000f64;
+
+    let workers = profile
+        .concurrency(elapsed)
+        .unwrap_or(settings.concurrency)
+        .max(1);
+
+    profile.rate(elapsed).map(|rate| rate / workers as f64)
+}
+
+fn profile_active(
+    profile: &Profile,
+    settings: &Settings,
+    id: usize,
+    elapsed_time: Duration,
+) -> bool {
+    let elapsed = elapsed_time.num_milliseconds() as f64 / 1000f64;
+
+    id <= profile.concurrency(elapsed).unwrap_or(settings.concurrency)
+}
+
+// Time left until the next request is due, re-checked as the rate changes between stages.
+fn profile_delay(
+    rate: Option<f64>,
+    previous_time: Option<DateTime<Utc>>,
+) -> Option<time::Duration> {
+    match (rate, previous_time) {
+        (Some(rate), _) if rate <= 0f64 => Some(PROFILE_DELAY),
+        (Some(rate), Some(previous_time)) => {
+            let interval = time::Duration::from_secs(1).div_f64(rate);
+            let due_time = previous_time + Duration::from_std(interval).expect("interval");
+
+            (due_time - Utc::now()).to_std().ok()
+        }
+        _ => None,
+    }
+}
+
+fn intended_time(rate: Option<f64>, previous_time: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (rate, previous_time) {
+        (Some(rate), Some(previous_time)) if rate > 0f64 => {
+            let interval = time::Duration::from_secs(1).div_f64(rate);
+
+            Some(previous_time + Duration::from_std(interval).expect("interval"))
+        }
+        _ => None,
+    }
+}
+
 fn total_check(total: Option<f64>, count: u64) -> bool {
     match total {
         Some(total) => count < total.ceil() as u64,